@@ -0,0 +1,404 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+use crate::{
+    clock::{Scheduler, CLOCK_TIMER},
+    cpu::VIP_FRAME_BUDGET,
+    vip::FRAME_CYCLES,
+    util::wav,
+    Emulator,
+};
+
+/**
+ * Tone used when exporting audio without host settings
+ */
+pub const AUDIO_FREQUENCY: f64 = 440.0;
+
+/**
+ * Longest stretch of emulated time rendered at once. Larger jumps happen when the time source changes, and are skipped.
+ */
+const AUDIO_MAX_STEP: f64 = 1.0;
+
+/**
+ * Tolerance on the number of samples due, so rounding errors on time do not delay a sample to the next tick
+ */
+const AUDIO_EPSILON: f64 = 1e-6;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /**
+     * Amplitude between -1 and 1 at a phase between 0 and 1
+     */
+    pub fn sample (&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square    =>  if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine      =>  (2.0 * PI * phase).sin(),
+            Waveform::Triangle  =>  1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+/**
+ * XO-CHIP pattern playback rate in bits per second for a pitch
+ */
+pub fn pattern_rate (pitch: u8) -> f64 {
+    4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0)
+}
+
+/**
+ * What the beeper plays during a tick
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tone {
+    Silent,
+
+    /**
+     * Configured waveform and frequency
+     */
+    Beeper,
+
+    /**
+     * XO-CHIP 128-bit pattern, looped at the rate given by the pitch
+     */
+    Pattern {
+        pattern: [u8; 16],
+        pitch: u8,
+    },
+}
+
+/**
+ * Average level, between -1 and 1, of a looped 1-bit pattern over `length` bits starting at `position`.
+ * Averaging rather than picking the nearest bit keeps high pitches from aliasing.
+ */
+fn pattern_level (pattern: &[u8; 16], position: f64, length: f64) -> f32 {
+    let bit = |n: usize| if pattern[n / 8 % 16] >> (7 - n % 8) & 1 == 1 { 1.0 } else { -1.0 };
+    let end = position + length;
+    let mut level = 0.0;
+    let mut from = position;
+
+    while from < end {
+        let to = (from.floor() + 1.0).min(end);
+
+        level += bit(from as usize) * (to - from);
+        from = to;
+    }
+
+    (level / length) as f32
+}
+
+/**
+ * Samples between -1 and 1 to 16-bit PCM
+ */
+pub fn pcm16 (samples: &[f32]) -> Vec<i16> {
+    samples.iter().map(|&sample| (sample * i16::MAX as f32) as i16).collect()
+}
+
+/**
+ * Synthesizes the beeper as PCM samples, following emulated time, into a ring buffer drained by the host
+ */
+pub struct Audio {
+    pub sample_rate: u32,
+    pub waveform: Waveform,
+    pub frequency: f64,
+    pub volume: f32,
+    pub buffer: VecDeque<f32>,
+
+    /**
+     * Samples kept before the oldest ones are dropped
+     */
+    pub capacity: usize,
+
+    /**
+     * Emulated time up to which samples were generated, in seconds
+     */
+    time: f64,
+
+    /**
+     * Fraction of a sample not generated yet
+     */
+    pending: f64,
+    phase: f32,
+
+    /**
+     * Bit of the XO-CHIP pattern being played
+     */
+    position: f64,
+}
+
+impl Audio {
+    pub fn new (sample_rate: u32, waveform: Waveform, frequency: f64, volume: f32, time: f64) -> Self {
+        Self {
+            sample_rate,
+            waveform,
+            frequency,
+            volume,
+            // Half a second of latency at most
+            buffer: VecDeque::with_capacity(sample_rate as usize / 2),
+            capacity: sample_rate as usize / 2,
+            time,
+            pending: 0.0,
+            phase: 0.0,
+            position: 0.0,
+        }
+    }
+
+    /**
+     * Generates samples up to the given emulated time, during which the beeper played the given tone.
+     * `speed` compresses time in turbo, and `pitch` multiplies the frequency.
+     */
+    pub fn advance (&mut self, time: f64, tone: Tone, speed: f64, pitch: f64) {
+        let elapsed = time - self.time;
+
+        self.time = time;
+
+        if !(0.0..=AUDIO_MAX_STEP).contains(&elapsed) {
+            return;
+        }
+
+        self.pending += elapsed * self.sample_rate as f64 / speed;
+
+        let step = (self.frequency * pitch / self.sample_rate as f64) as f32;
+
+        while self.pending >= 1.0 - AUDIO_EPSILON {
+            self.pending -= 1.0;
+
+            let sample = match tone {
+                Tone::Silent                        =>  {
+                    self.phase = 0.0;
+                    self.position = 0.0;
+                    0.0
+                },
+                Tone::Beeper                        =>  {
+                    let sample = self.waveform.sample(self.phase);
+                    self.phase = (self.phase + step).fract();
+                    sample
+                },
+                Tone::Pattern { pattern, pitch: n } =>  {
+                    let length = pattern_rate(n) * pitch / self.sample_rate as f64;
+                    let sample = pattern_level(&pattern, self.position, length);
+                    self.position = (self.position + length) % 128.0;
+                    sample
+                },
+            } * self.volume;
+
+            if self.buffer.len() >= self.capacity {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back(sample);
+        }
+    }
+
+    /**
+     * Keeps every sample, for exports
+     */
+    pub fn unbounded (mut self) -> Self {
+        self.capacity = usize::MAX;
+        self
+    }
+
+    /**
+     * Restarts from the given emulated time, after a reset
+     */
+    pub fn rebase (&mut self, time: f64) {
+        self.time = time;
+        self.pending = 0.0;
+    }
+}
+
+impl Emulator {
+    /**
+     * Emulated time in seconds, precise to the instruction
+     */
+    pub (crate) fn audio_time (&self) -> f64 {
+        let frames = self.frame_count() as f64;
+
+        match (&self.vip, self.cpu.scheduler) {
+            (Some(vip), _)              =>  (frames + vip.video.cycle as f64 / FRAME_CYCLES as f64) / CLOCK_TIMER,
+            (None, Scheduler::Clock)    =>  self.clock.time,
+            (None, Scheduler::Ipf(ipf)) =>  (frames + self.cpu.frame_cycles as f64 / ipf as f64) / CLOCK_TIMER,
+            (None, Scheduler::Vip)      =>  (frames + (self.cpu.frame_cycles as f64 / VIP_FRAME_BUDGET as f64).min(1.0)) / CLOCK_TIMER,
+        }
+    }
+
+    /**
+     * What the beeper currently plays
+     */
+    pub (crate) fn tone (&self) -> Tone {
+        match self.cpu.pattern {
+            _ if !self.beep()                       =>  Tone::Silent,
+            Some(pattern) if self.vip.is_none()     =>  Tone::Pattern { pattern, pitch: self.cpu.pitch },
+            _                                       =>  Tone::Beeper,
+        }
+    }
+
+    /**
+     * Unbounded synthesis from now on, with the host waveform, frequency and volume if set
+     */
+    pub (crate) fn audio_export (&self, sample_rate: u32) -> Audio {
+        let (waveform, frequency, volume) = self.audio.as_ref().map_or((Waveform::Square, AUDIO_FREQUENCY, 1.0), |audio| (audio.waveform, audio.frequency, audio.volume));
+
+        Audio::new(sample_rate, waveform, frequency, volume, self.audio_time()).unbounded()
+    }
+
+    /**
+     * Runs frames and returns the sound they made, with the host waveform, frequency and volume if set, but regardless of turbo.
+     * Host samples are not generated meanwhile.
+     */
+    pub fn audio_render (&mut self, frames: usize, sample_rate: u32) -> Vec<f32> {
+        let host = self.audio.replace(self.audio_export(sample_rate));
+        let turbo = self.turbo.take();
+
+        for _ in 0..frames {
+            self.cycle_until_timer();
+        }
+
+        let samples = self.audio.take().map(|audio| audio.buffer.into()).unwrap_or_default();
+        let time = self.audio_time();
+
+        self.turbo = turbo;
+        self.audio = host;
+        if let Some(audio) = &mut self.audio {
+            audio.rebase(time);
+        }
+
+        samples
+    }
+
+    /**
+     * Called after every tick, with the tone from before it, so edges fall on the instruction or timer decrement that caused them
+     */
+    pub (crate) fn audio_advance (&mut self, tone: Tone) {
+        let (time, speed, pitch) = (self.audio_time(), self.turbo_speed() as f64, self.beep_pitch());
+
+        if let Some(audio) = &mut self.audio {
+            audio.advance(time, tone, speed, pitch);
+        }
+
+        // Recordings play back in real time, whatever the turbo
+        if let Some(video) = self.video.as_mut().filter(|video| !video.truncated()) {
+            video.audio.advance(time, tone, 1.0, 1.0);
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Generates the beeper as samples, to be read with `audio_samples`
+     */
+    pub fn set_audio (&mut self, sample_rate: u32, waveform: Waveform, frequency: f64, volume: f32) {
+        if sample_rate == 0 || !(frequency.is_finite() && frequency > 0.0) {
+            log::warn!("Ignoring invalid audio sample rate {} and frequency {}", sample_rate, frequency);
+            return;
+        }
+
+        self.audio = Some(Audio::new(sample_rate, waveform, frequency, volume.clamp(0.0, 1.0), self.audio_time()));
+    }
+
+    pub fn clear_audio (&mut self) {
+        self.audio = None;
+    }
+
+    /**
+     * Runs the given number of frames, and returns the sound they made as a 16-bit mono WAV file.
+     * Combined with movie playback, this makes audio reproducible.
+     */
+    pub fn audio_export_wav (&mut self, frames: usize, sample_rate: u32) -> Vec<u8> {
+        wav::encode(sample_rate, &pcm16(&self.audio_render(frames, sample_rate)))
+    }
+
+    /**
+     * Number of samples waiting to be read
+     */
+    pub fn audio_available (&self) -> usize {
+        self.audio.as_ref().map_or(0, |audio| audio.buffer.len())
+    }
+
+    /**
+     * Drains up to `max` samples, between -1 and 1
+     */
+    pub fn audio_samples (&mut self, max: usize) -> Vec<f32> {
+        match &mut self.audio {
+            Some(audio) =>  {
+                let count = max.min(audio.buffer.len());
+                audio.buffer.drain(..count).collect()
+            },
+            None        =>  Vec::new(),
+        }
+    }
+}
+
+#[test]
+fn samples () {
+    // Beep for 2 frames, then loop
+    let mut emulator = Emulator::new(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+
+    emulator.set_instructions_per_frame(10);
+    emulator.set_audio(6000, Waveform::Square, 500.0, 0.5);
+    for _ in 0..4 {
+        emulator.frame();
+    }
+
+    let samples = emulator.audio_samples(usize::MAX);
+    let on = samples.iter().filter(|&&sample| sample != 0.0).count();
+
+    assert_eq!(samples.len(), 4 * 100);
+    // From the second instruction of the first frame to the end of the second frame
+    assert_eq!(on, 200 - 20);
+    assert_eq!(samples[20..32].iter().filter(|&&sample| sample == 0.5).count(), 6);
+    assert_eq!(emulator.audio_available(), 0);
+}
+
+#[test]
+fn pattern () {
+    assert_eq!(pattern_rate(64), 4000.0);
+    assert_eq!(pattern_rate(112), 8000.0);
+
+    // Alternating bytes of ones and zeroes, played at 4000 bits per second from the second frame
+    let mut emulator = Emulator::new(&[0xA2, 0x0A, 0xF0, 0x02, 0x60, 0x02, 0xF0, 0x18, 0x12, 0x08, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00]).unwrap();
+
+    emulator.set_instructions_per_frame(4);
+    emulator.set_audio(1000, Waveform::Square, 500.0, 1.0);
+    emulator.frame();
+    emulator.frame();
+
+    let samples = emulator.audio_samples(usize::MAX);
+
+    assert_eq!(samples.len(), 33);
+    assert_eq!(&samples[..16], &[0.0; 16]);
+    // 4 bits per sample
+    assert_eq!(&samples[16..22], &[1.0, 1.0, -1.0, -1.0, 1.0, 1.0]);
+}
+
+#[test]
+fn pattern_out_of_bounds () {
+    // Load a pattern that would end past the end of memory
+    let mut emulator = Emulator::new(&[0xAF, 0xF8, 0xF0, 0x02]).unwrap();
+
+    emulator.frame();
+    assert_eq!(emulator.state(), crate::RunState::Error);
+    assert_eq!(emulator.error(), Some(String::from("Memory access out of bounds from I=FF8 at 202")));
+}
+
+#[test]
+fn export () {
+    // Wait 2 frames on the delay timer, then beep for 2 frames
+    let mut emulator = Emulator::new(&[0x61, 0x02, 0xF1, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x60, 0x02, 0xF0, 0x18, 0x12, 0x0E]).unwrap();
+
+    emulator.set_instructions_per_frame(100);
+    emulator.set_audio(44100, Waveform::Sine, 440.0, 1.0);
+
+    let samples = emulator.audio_render(5, 6000);
+    let beeping = samples.chunks(100).map(|frame| frame.iter().any(|&sample| sample != 0.0)).collect::<Vec<bool>>();
+
+    assert_eq!(beeping, vec![false, false, true, true, false]);
+    assert_eq!(emulator.audio_available(), 0);
+    assert_eq!(emulator.audio_export_wav(1, 8000).len(), 44 + 8000 / 60 * 2);
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use crate::{
+    clock::CLOCK_CPU,
+    memory::RomError,
+    octo::{self, OctoError},
+    palette::Palette,
+    quirks::Quirks,
+    util::{gif::{self, GifError}, sha1::sha1_hex},
+    Emulator,
+};
+
+/**
+ * Bits of payload carried by each pixel, in the low bits of its color index
+ */
+const PAYLOAD_BITS: usize = 2;
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    Gif(GifError),
+
+    /**
+     * Payload could not be decoded
+     */
+    Format(String),
+
+    /**
+     * Octo source code of the program does not assemble
+     */
+    Source(OctoError),
+
+    /**
+     * Program does not fit in memory
+     */
+    Rom(RomError),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Gif(err)        =>  write!(f, "Invalid cartridge: {}", err),
+            CartridgeError::Format(reason)  =>  write!(f, "Invalid cartridge: {}", reason),
+            CartridgeError::Source(err)     =>  write!(f, "Invalid cartridge program: {}", err),
+            CartridgeError::Rom(err)        =>  write!(f, "Invalid cartridge program: {}", err),
+        }
+    }
+}
+
+impl From<CartridgeError> for JsValue {
+    fn from (err: CartridgeError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/**
+ * Settings saved by Octo along with the program. Options this emulator has no use for are ignored.
+ */
+#[derive(serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OctoOptions {
+    pub tickrate: Option<u32>,
+    pub background_color: Option<String>,
+    pub fill_color: Option<String>,
+    pub fill_color2: Option<String>,
+    pub blend_color: Option<String>,
+    #[serde(default)]
+    pub shift_quirks: bool,
+    #[serde(default)]
+    pub load_store_quirks: bool,
+    #[serde(default)]
+    pub clip_quirks: bool,
+    #[serde(default)]
+    pub jump_quirks: bool,
+    #[serde(default)]
+    pub logic_quirks: bool,
+    #[serde(default)]
+    pub v_blank_quirks: bool,
+    #[serde(default)]
+    pub vf_order_quirks: bool,
+}
+
+impl OctoOptions {
+    pub fn quirks (&self) -> Quirks {
+        Quirks {
+            shift: self.shift_quirks,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: self.load_store_quirks,
+            wrap: !self.clip_quirks,
+            jump: self.jump_quirks,
+            logic: self.logic_quirks,
+            vblank: self.v_blank_quirks,
+            key_release: false,
+            flag_last: !self.vf_order_quirks,
+        }
+    }
+
+    /**
+     * Colors are given as `#RRGGBB`, missing ones come from the default palette
+     */
+    pub fn palette (&self, default: Palette) -> Palette {
+        let color = |color: &Option<String>, default: u32| color
+            .as_ref()
+            .and_then(|color| u32::from_str_radix(color.trim_start_matches('#'), 16).ok())
+            .unwrap_or(default);
+
+        Palette::new(
+            color(&self.background_color, default.background),
+            color(&self.fill_color, default.foreground),
+            color(&self.fill_color2, default.plane2),
+            color(&self.blend_color, default.blend),
+        )
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Payload {
+    options: OctoOptions,
+
+    /**
+     * Octo source code as saved by Octo, or the ROM as an array of bytes
+     */
+    program: serde_json::Value,
+}
+
+/**
+ * Program and settings shared as an Octo cartridge: an animated GIF whose pixels carry a JSON payload.
+ * The payload is assumed to be spread over the frames in order, two bits per pixel in the low bits of the color index,
+ * most significant bits first, and to start with its length as a 32-bit big-endian integer.
+ * It is an object with `options` and `program`, the Octo source code which is assembled here.
+ */
+pub struct Cartridge {
+    pub rom: Vec<u8>,
+    pub options: OctoOptions,
+}
+
+impl Cartridge {
+    pub fn parse (data: &[u8]) -> Result<Self, CartridgeError> {
+        let images = gif::decode(data).map_err(CartridgeError::Gif)?;
+        let bytes: Vec<u8> = images
+            .iter()
+            .flat_map(|image| image.indices.chunks_exact(8 / PAYLOAD_BITS))
+            .map(|pixels| pixels.iter().fold(0, |byte, index| byte << PAYLOAD_BITS | index & ((1 << PAYLOAD_BITS) - 1)))
+            .collect();
+
+        let length = match bytes.get(..4) {
+            Some(length)    =>  u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize,
+            None            =>  return Err(CartridgeError::Format(String::from("no payload"))),
+        };
+        let json = 4usize.checked_add(length)
+            .and_then(|end| bytes.get(4 .. end))
+            .ok_or_else(|| CartridgeError::Format(String::from("payload is truncated")))?;
+        let payload: Payload = serde_json::from_slice(json).map_err(|err| CartridgeError::Format(err.to_string()))?;
+
+        let rom = match payload.program {
+            serde_json::Value::String(source)   =>  octo::assemble(&source).map_err(CartridgeError::Source)?,
+            program                             =>  serde_json::from_value(program).map_err(|err| CartridgeError::Format(err.to_string()))?,
+        };
+
+        Ok(Self { rom, options: payload.options })
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Loads the ROM of an Octo cartridge, with its speed, quirks and colors
+     */
+    pub fn from_cartridge (data: &[u8]) -> Result<Emulator, CartridgeError> {
+        let cartridge = Cartridge::parse(data)?;
+        let mut emulator = Emulator::with_cpu_rate(&cartridge.rom, CLOCK_CPU).map_err(CartridgeError::Rom)?;
+
+        if let Some(tickrate) = cartridge.options.tickrate {
+            emulator.set_instructions_per_frame(tickrate);
+        }
+
+        emulator.set_quirks(cartridge.options.quirks());
+        emulator.palettes.overrides.insert(sha1_hex(&cartridge.rom), cartridge.options.palette(emulator.palettes.default));
+        emulator.update_palette();
+        Ok(emulator)
+    }
+}
+
+#[cfg(test)]
+fn cartridge (payload: &str) -> Vec<u8> {
+    cartridge_bytes(&[&(payload.len() as u32).to_be_bytes(), payload.as_bytes()].concat())
+}
+
+/**
+ * Cartridge carrying the bytes as they are, including the length header
+ */
+#[cfg(test)]
+fn cartridge_bytes (bytes: &[u8]) -> Vec<u8> {
+    let mut indices: Vec<u8> = bytes.iter().flat_map(|byte| (0..4).rev().map(move |n| byte >> (n * 2) & 0x3 | 0x4)).collect();
+
+    indices.resize(64 * 32, 0);
+    gif::encode(64, 32, &[[0, 0, 0]; 8], &[
+        gif::Frame { indices: &indices[.. 64 * 16], delay: 10 },
+        gif::Frame { indices: &indices[64 * 16 ..], delay: 10 },
+    ])
+}
+
+#[test]
+fn load () {
+    let options = r##"{ "tickrate": 20, "fillColor": "#FFCC00", "clipQuirks": true, "shiftQuirks": true }"##;
+    let mut emulator = Emulator::from_cartridge(&cartridge(&format!(r#"{{ "options": {}, "program": [18, 0] }}"#, options))).unwrap();
+
+    assert_eq!(emulator.rom, vec![0x12, 0x00]);
+    assert_eq!(emulator.instructions_per_frame(), Some(20));
+    assert!(emulator.quirks().shift && !emulator.quirks().wrap && emulator.quirks().flag_last);
+    assert_eq!(emulator.palette().foreground, 0xFFCC00);
+    emulator.cycle_until_timer();
+
+    assert!(matches!(Cartridge::parse(&cartridge(&format!(r#"{{ "options": {}, "program": ": main jump" }}"#, options))), Err(CartridgeError::Source(_))));
+    assert!(matches!(Cartridge::parse(&cartridge("{")), Err(CartridgeError::Format(_))));
+}
+
+#[test]
+fn truncated () {
+    // Largest length a payload can claim, which overflows the end of the payload on 32-bit targets
+    let err = Cartridge::parse(&cartridge_bytes(&[0xFF, 0xFF, 0xFF, 0xFF, b'{', b'}'])).err().unwrap();
+
+    assert_eq!(err.to_string(), "Invalid cartridge: payload is truncated");
+}
+
+#[test]
+fn source () {
+    // Count in V1 while V0 is below V2, which relies on VF being written after the result of 8XY5
+    let program = r#"\n: main\n  v2 := 5\n  loop\n    while v0 < v2\n    v0 += 1\n    v1 += 1\n  again\n  loop again\n"#;
+    let mut emulator = Emulator::from_cartridge(&cartridge(&format!(r#"{{ "options": {{ "tickrate": 100 }}, "program": "{}" }}"#, program))).unwrap();
+
+    emulator.frame();
+    assert_eq!(emulator.rom, octo::assemble(&program.replace("\\n", "\n")).unwrap());
+    assert_eq!((emulator.cpu.v[0], emulator.cpu.v[1]), (5, 5));
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use crate::{
+    memory::{Memory, MEMORY_SIZE},
+    Emulator,
+};
+
+/**
+ * When cheats are written back to memory
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CheatTiming {
+    Cycle,
+    Frame,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CheatError {
+    /**
+     * Line could not be parsed
+     */
+    Syntax(usize),
+
+    /**
+     * Address does not fit in memory
+     */
+    Address(u16),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatError::Syntax(line)        =>  write!(f, "Invalid cheat on line {}", line),
+            CheatError::Address(address)    =>  write!(f, "Cheat address {:03X} is out of memory", address),
+        }
+    }
+}
+
+/**
+ * Freezes a memory location to a given value
+ */
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+
+    /**
+     * If set, the value is only written when memory currently holds this one
+     */
+    pub compare: Option<u8>,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    pub fn new (address: u16, value: u8, compare: Option<u8>) -> Result<Self, CheatError> {
+        if address as usize >= MEMORY_SIZE {
+            return Err(CheatError::Address(address));
+        }
+
+        Ok(Self {
+            address,
+            value,
+            compare,
+            enabled: true,
+        })
+    }
+
+    pub fn apply (&self, memory: &mut Memory) {
+        let cell = &mut memory.ram[self.address as usize];
+
+        if self.enabled && self.compare.unwrap_or(*cell) == *cell {
+            *cell = self.value;
+        }
+    }
+}
+
+pub struct Cheats {
+    pub list: Vec<Cheat>,
+    pub timing: CheatTiming,
+}
+
+impl Default for Cheats {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Cheats {
+    pub fn new () -> Self {
+        Self {
+            list: Vec::new(),
+            timing: CheatTiming::Frame,
+        }
+    }
+
+    pub fn apply (&self, memory: &mut Memory) {
+        for cheat in &self.list {
+            cheat.apply(memory);
+        }
+    }
+
+    /**
+     * One cheat per line, written as AAA:VV or AAA:VV:CC in hexadecimal (address, value, compare).
+     * A leading '-' marks a disabled cheat. Blank lines and lines starting with '#' are ignored.
+     */
+    pub fn parse (text: &str) -> Result<Vec<Cheat>, CheatError> {
+        let mut cheats = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (enabled, line) = match line.strip_prefix('-') {
+                Some(rest)  =>  (false, rest),
+                None        =>  (true, line),
+            };
+            let fields = line.split(':').map(|field| u16::from_str_radix(field.trim(), 16)).collect::<Result<Vec<u16>, _>>();
+
+            let mut cheat = match fields.as_deref() {
+                Ok(&[address, value]) if value <= 0xFF                                   =>  Cheat::new(address, value as u8, None)?,
+                Ok(&[address, value, compare]) if value <= 0xFF && compare <= 0xFF       =>  Cheat::new(address, value as u8, Some(compare as u8))?,
+                _                                                                        =>  return Err(CheatError::Syntax(n + 1)),
+            };
+
+            cheat.enabled = enabled;
+            cheats.push(cheat);
+        }
+
+        Ok(cheats)
+    }
+
+    pub fn export (&self) -> String {
+        self.list.iter().map(|cheat| {
+            let prefix = if cheat.enabled { "" } else { "-" };
+
+            match cheat.compare {
+                Some(compare)   =>  format!("{}{:03X}:{:02X}:{:02X}\n", prefix, cheat.address, cheat.value, compare),
+                None            =>  format!("{}{:03X}:{:02X}\n", prefix, cheat.address, cheat.value),
+            }
+        }).collect()
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    pub fn cheat_add (&mut self, address: u16, value: u8, compare: Option<u8>) -> Result<usize, JsValue> {
+        let cheat = Cheat::new(address, value, compare).map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        self.cheats.list.push(cheat);
+        Ok(self.cheats.list.len() - 1)
+    }
+
+    pub fn cheat_remove (&mut self, index: usize) {
+        if index < self.cheats.list.len() {
+            self.cheats.list.remove(index);
+        }
+    }
+
+    pub fn cheat_enable (&mut self, index: usize, enabled: bool) {
+        if let Some(cheat) = self.cheats.list.get_mut(index) {
+            cheat.enabled = enabled;
+        }
+    }
+
+    pub fn cheat_clear (&mut self) {
+        self.cheats.list.clear();
+    }
+
+    /**
+     * Cheats as JSON
+     */
+    pub fn cheat_list (&self) -> String {
+        serde_json::to_string(&self.cheats.list).unwrap()
+    }
+
+    pub fn set_cheat_timing (&mut self, timing: CheatTiming) {
+        self.cheats.timing = timing;
+    }
+
+    /**
+     * Appends cheats from their textual representation
+     */
+    pub fn cheat_import (&mut self, text: &str) -> Result<(), JsValue> {
+        let cheats = Cheats::parse(text).map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        self.cheats.list.extend(cheats);
+        Ok(())
+    }
+
+    pub fn cheat_export (&self) -> String {
+        self.cheats.export()
+    }
+}
+
+#[test]
+fn parse () {
+    let cheats = Cheats::parse("# Infinite lives\n3A0:05\n-3A1:FF:00\n").unwrap();
+
+    assert_eq!(cheats, vec![
+        Cheat { address: 0x3A0, value: 0x05, compare: None, enabled: true },
+        Cheat { address: 0x3A1, value: 0xFF, compare: Some(0x00), enabled: false },
+    ]);
+    assert_eq!(Cheats::parse("3A0:105"), Err(CheatError::Syntax(1)));
+    assert_eq!(Cheats::parse("1000:00"), Err(CheatError::Address(0x1000)));
+    assert_eq!(Cheats { list: cheats, timing: CheatTiming::Frame }.export(), "3A0:05\n-3A1:FF:00\n");
+}
+
+#[test]
+fn apply () {
+    let mut memory = Memory::new(&[0x12, 0x00], crate::memory::PROGRAM_START).unwrap();
+    let mut cheat = Cheat::new(0x300, 0x42, Some(0x01)).unwrap();
+
+    cheat.apply(&mut memory);
+    assert_eq!(memory.ram[0x300], 0x00);
+
+    memory.ram[0x300] = 0x01;
+    cheat.apply(&mut memory);
+    assert_eq!(memory.ram[0x300], 0x42);
+
+    cheat.enabled = false;
+    memory.ram[0x300] = 0x01;
+    cheat.apply(&mut memory);
+    assert_eq!(memory.ram[0x300], 0x01);
+}
@@ -0,0 +1,225 @@
+use wasm_bindgen::prelude::*;
+use crate::{
+    display::{DISPLAY_WIDTH, DISPLAY_HEIGHT},
+    input::{InputError, Keypad},
+    memory::{RomError, RomInfo, PROGRAM_START, PROGRAM_START_CHIP8X},
+    palette::Palette,
+    Emulator,
+};
+
+/**
+ * Colors are set for columns of 8 pixels
+ */
+pub const ZONE_WIDTH: usize = 8;
+
+/**
+ * BXY0 colors zones of 4 rows, while BXYN colors single rows
+ */
+pub const ZONE_HEIGHT: usize = 4;
+pub const COLUMNS: usize = DISPLAY_WIDTH / ZONE_WIDTH;
+
+/**
+ * Background colors of the VP-590 color board, stepped through in order by 02A0
+ */
+pub const BACKGROUNDS: [u32; 4] = [0x000080, 0x000000, 0x008000, 0x800000];
+
+/**
+ * Foreground colors, indexed by the 3-bit color of a zone (bit 0: red, bit 1: blue, bit 2: green)
+ */
+pub const FOREGROUNDS: [u32; 8] = [0x000000, 0xFF0000, 0x0000FF, 0xFF00FF, 0x00FF00, 0xFFFF00, 0x00FFFF, 0xFFFFFF];
+
+/**
+ * Zones start red, like after the CHIP-8X interpreter starts
+ */
+const DEFAULT_COLOR: u8 = 1;
+
+/**
+ * Color attribute memory of the VP-590 color board, layered over the monochrome display
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Colors {
+    /**
+     * Foreground color of each column, for every row
+     */
+    pub zones: [u8; COLUMNS * DISPLAY_HEIGHT],
+
+    /**
+     * Index into `BACKGROUNDS`
+     */
+    pub background: usize,
+}
+
+impl Default for Colors {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Colors {
+    pub fn new () -> Self {
+        Self {
+            zones: [DEFAULT_COLOR; COLUMNS * DISPLAY_HEIGHT],
+            background: 0,
+        }
+    }
+
+    /**
+     * 02A0
+     */
+    pub fn step_background (&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    /**
+     * Colors a rectangle of columns and rows, clipped to the screen
+     */
+    pub fn fill (&mut self, columns: std::ops::Range<usize>, rows: std::ops::Range<usize>, color: u8) {
+        for row in rows.start.min(DISPLAY_HEIGHT) .. rows.end.min(DISPLAY_HEIGHT) {
+            for column in columns.start.min(COLUMNS) .. columns.end.min(COLUMNS) {
+                self.zones[row * COLUMNS + column] = color & 0x7;
+            }
+        }
+    }
+
+    /**
+     * BXY0: the low nibble of VX is the first column and its high nibble the number of columns after it.
+     * VY gives zones of 4 rows the same way.
+     */
+    pub fn fill_zones (&mut self, vx: u8, vy: u8, color: u8) {
+        let (column, row) = ((vx & 0xF) as usize, (vy & 0xF) as usize);
+        let (width, height) = ((vx >> 4) as usize + 1, (vy >> 4) as usize + 1);
+
+        self.fill(column .. column + width, row * ZONE_HEIGHT .. (row + height) * ZONE_HEIGHT, color);
+    }
+
+    /**
+     * BXYN: colors N rows from row VY, in the column of pixel VX
+     */
+    pub fn fill_rows (&mut self, vx: u8, vy: u8, n: u8, color: u8) {
+        let (column, row) = ((vx as usize % DISPLAY_WIDTH) / ZONE_WIDTH, vy as usize % DISPLAY_HEIGHT);
+
+        self.fill(column .. column + 1, row .. row + n as usize, color);
+    }
+
+    /**
+     * Two-color palette of a pixel, from the background and the color of its zone
+     */
+    pub fn palette (&self, index: usize) -> Palette {
+        let (x, y) = (index % DISPLAY_WIDTH, index / DISPLAY_WIDTH % DISPLAY_HEIGHT);
+        let foreground = FOREGROUNDS[self.zones[y * COLUMNS + x / ZONE_WIDTH] as usize];
+
+        Palette::new(BACKGROUNDS[self.background], foreground, foreground, foreground)
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Switches to CHIP-8X, with the VP-590 color board and a second keypad, and restarts the ROM at 0x300
+     */
+    pub fn load_chip8x (&mut self) -> Result<(), RomError> {
+        RomInfo::new(&self.rom, PROGRAM_START_CHIP8X)?;
+        self.vip = None;
+        self.keypad2 = Some(Keypad::new());
+        self.load_address = PROGRAM_START_CHIP8X;
+        self.reset();
+        Ok(())
+    }
+
+    /**
+     * Switches back to monochrome CHIP-8 and restarts the ROM at 0x200
+     */
+    pub fn unload_chip8x (&mut self) {
+        self.keypad2 = None;
+        self.load_address = PROGRAM_START;
+        self.reset();
+    }
+
+    /**
+     * Updates a key of the second keypad, read by EXF2 and EXF5. Live input is ignored while a movie is playing.
+     */
+    pub fn update_key2 (&mut self, key: usize, state: bool) -> Result<(), InputError> {
+        let (frame, cycle) = (self.frame_count(), self.cycle_count());
+
+        if self.movie_playing() {
+            return Ok(());
+        }
+
+        match &mut self.keypad2 {
+            Some(keypad)    =>  keypad.set(key, state, frame, cycle)?,
+            None            =>  return Err(InputError::Keypad2),
+        }
+
+        self.movie_input(key, state, true);
+        Ok(())
+    }
+}
+
+#[test]
+fn colors () {
+    let rom = [
+        0x02, 0xA0, // BGCOL
+        0x60, 0x11, // LD V0, 11
+        0x61, 0x02, // LD V1, 02
+        0x62, 0x00, // LD V2, 00
+        0xB0, 0x20, // COLOR V0, V2: columns 1-2, rows 0-3 in blue
+        0x63, 0x38, // LD V3, 38
+        0x64, 0x06, // LD V4, 06
+        0xB3, 0x22, // COLOR V3, V2, 2: column 7, rows 0-1 in cyan
+        0x50, 0x01, // ADDN V0, V0
+        0x64, 0x05, // LD V4, 05
+        0xE4, 0xF2, // SKP2 V4
+        0x13, 0x1A, // JMP 31A
+        0x65, 0x01, // LD V5, 01
+        0x13, 0x1A, // JMP 31A
+    ];
+    let mut emulator = Emulator::new(&rom).unwrap();
+
+    emulator.load_chip8x().unwrap();
+    assert_eq!(emulator.machine(), crate::vip::Machine::Chip8X);
+    emulator.update_key2(5, true).unwrap();
+    for _ in 0..3 {
+        emulator.cycle_until_timer();
+    }
+
+    let colors = emulator.display.colors.as_ref().unwrap();
+    assert_eq!(emulator.cpu.v[0x0], 0x22);
+    assert_eq!(emulator.cpu.v[0x5], 0x01);
+    assert_eq!(colors.background, 1);
+    assert_eq!(colors.zones[0], DEFAULT_COLOR);
+    assert_eq!(colors.zones[1..3], [2, 2]);
+    assert_eq!(colors.zones[3 * COLUMNS + 1], 2);
+    assert_eq!(colors.zones[4 * COLUMNS + 1], DEFAULT_COLOR);
+    assert_eq!(colors.zones[COLUMNS + 7], 6);
+    assert_eq!(colors.zones[2 * COLUMNS + 7], DEFAULT_COLOR);
+    assert_eq!(colors.palette(8).foreground, 0x0000FF);
+
+    emulator.display.draw_pixel(8, 0, true);
+    emulator.refresh_framebuffer();
+    assert_eq!(&emulator.output.buffer[..4], &[0x00, 0x00, 0x00, 0xFF]);
+    assert_eq!(&emulator.output.buffer[32..36], &[0x00, 0x00, 0xFF, 0xFF]);
+
+    emulator.unload_chip8x();
+    assert!(emulator.display.colors.is_none());
+    assert_eq!(emulator.machine(), crate::vip::Machine::Chip8);
+}
+
+#[test]
+fn add_nibbles () {
+    let rom = [
+        0x60, 0xF8, // LD V0, F8
+        0x61, 0x19, // LD V1, 19
+        0x50, 0x11, // ADDN V0, V1
+        0x62, 0xFF, // LD V2, FF
+        0x52, 0x21, // ADDN V2, V2
+        0x13, 0x0A, // JMP 30A
+    ];
+    let mut emulator = Emulator::new(&rom).unwrap();
+
+    emulator.load_chip8x().unwrap();
+    for _ in 0..3 {
+        emulator.cycle_until_timer();
+    }
+
+    assert_eq!((emulator.cpu.v[0x0], emulator.cpu.v[0x2]), (0x01, 0xEE));
+}
@@ -1,6 +1,27 @@
 pub const CLOCK_CPU: f64    =   500.0;
 pub const CLOCK_TIMER: f64  =   60.0;
 
+/**
+ * How CPU cycles are spread between timer decrements
+ */
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Scheduler {
+    /**
+     * CPU and timers are divided from the master clock time
+     */
+    Clock,
+
+    /**
+     * A fixed number of instructions per 60Hz frame, counted in integers so frame boundaries are exact
+     */
+    Ipf(u32),
+
+    /**
+     * Each instruction consumes its COSMAC VIP machine cycle cost from a per-frame budget, and drawing waits for the next frame
+     */
+    Vip,
+}
+
 #[derive(serde::Serialize)]
 pub struct Clock {
     pub rate: f64,
@@ -34,6 +55,12 @@ impl Clock {
 pub struct ClockDivider {
     pub rate: f64,
     pub cycles: usize,
+
+    /**
+     * Cycles run and clock time when the rate was last changed
+     */
+    base: usize,
+    since: f64,
 }
 
 impl ClockDivider {
@@ -41,6 +68,8 @@ impl ClockDivider {
         Self {
             rate,
             cycles: 0,
+            base: 0,
+            since: 0.0,
         }
     }
 
@@ -49,7 +78,28 @@ impl ClockDivider {
      */
     pub fn tick (&mut self, clock: f64) -> bool {
         let previous = self.cycles;
-        self.cycles = (clock / (1.0 / self.rate)) as usize;
+        self.cycles = self.base + ((clock - self.since) / (1.0 / self.rate)) as usize;
         self.cycles != previous
     }
+
+    /**
+     * Changes rate from the given clock time on, without affecting cycles already run
+     */
+    pub fn set_rate (&mut self, rate: f64, clock: f64) {
+        self.rate = rate;
+        self.base = self.cycles;
+        self.since = clock;
+    }
+}
+
+#[test]
+fn divider_rate () {
+    let mut divider = ClockDivider::new(4.0);
+
+    divider.tick(0.5);
+    assert_eq!(divider.cycles, 2);
+
+    divider.set_rate(8.0, 0.5);
+    divider.tick(0.75);
+    assert_eq!(divider.cycles, 4);
 }
@@ -1,12 +1,52 @@
-use js_sys::Math;
+use std::{fmt, ops::Range};
 use crate::{
-    memory::{Memory, PROGRAM_START, RESERVED_START},
+    memory::{Memory, MEMORY_SIZE, PROGRAM_START, RESERVED_START},
     display::Display,
     input::Keypad,
-    clock::ClockDivider,
-    cpu::instruction::Instruction,
+    clock::{ClockDivider, Scheduler},
+    cpu::{
+        instruction::Instruction,
+        timing::{vip_cycles, VIP_FRAME_BUDGET},
+    },
+    quirks::Quirks,
+    rng::Rng,
 };
 
+#[derive(Debug, PartialEq)]
+pub enum CpuError {
+    UnknownInstruction(u16, u16),
+    StackOverflow(u16),
+    StackUnderflow(u16),
+
+    /**
+     * Instruction at the address reads or writes past the end of memory from I
+     */
+    MemoryOutOfBounds(u16, u16),
+
+    /**
+     * Program counter points past the end of memory
+     */
+    PcOutOfBounds(u16),
+
+    /**
+     * Instruction at the address checks a key that is not on the keypad
+     */
+    KeyOutOfBounds(u16, u8),
+}
+
+impl fmt::Display for CpuError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::UnknownInstruction(address, opcode)   =>  write!(f, "Unknown instruction {:04X} at {:03X}", opcode, address),
+            CpuError::StackOverflow(address)                =>  write!(f, "Stack overflow at {:03X}", address),
+            CpuError::StackUnderflow(address)               =>  write!(f, "Stack underflow at {:03X}", address),
+            CpuError::MemoryOutOfBounds(address, i)         =>  write!(f, "Memory access out of bounds from I={:03X} at {:03X}", i, address),
+            CpuError::PcOutOfBounds(address)                =>  write!(f, "Program counter out of bounds at {:03X}", address),
+            CpuError::KeyOutOfBounds(address, key)          =>  write!(f, "Key {:02X} out of bounds at {:03X}", key, address),
+        }
+    }
+}
+
 pub struct Cpu {
     pub v: [u8; 16],
     pub i: u16,
@@ -17,10 +57,39 @@ pub struct Cpu {
     pub st: u8,
     pub clock: ClockDivider,
     pub clock_timer: ClockDivider,
+    pub scheduler: Scheduler,
+
+    /**
+     * Cycles run since the last timer decrement, when scheduling by instructions per frame.
+     * VIP machine cycles spent in the current frame, when scheduling by VIP timing.
+     */
+    pub frame_cycles: u32,
+
+    /**
+     * Set after drawing when the vblank quirk is enabled, until the next timer decrement
+     */
+    pub vblank_wait: bool,
+    pub quirks: Quirks,
+    pub rng: Rng,
+
+    /**
+     * Keypad sequence number when FX0A started waiting for a key release
+     */
+    pub key_wait: Option<u64>,
+
+    /**
+     * XO-CHIP 1-bit audio pattern loaded by F002, played instead of the beeper tone once set
+     */
+    pub pattern: Option<[u8; 16]>,
+
+    /**
+     * XO-CHIP pattern playback pitch set by FX3A
+     */
+    pub pitch: u8,
 }
 
 impl Cpu {
-    pub fn new () -> Self {
+    pub fn new (seed: u64) -> Self {
         Self {
             v: [0; 16],
             i: 0,
@@ -31,21 +100,71 @@ impl Cpu {
             st: 0,
             clock: ClockDivider::new(crate::clock::CLOCK_CPU),
             clock_timer: ClockDivider::new(crate::clock::CLOCK_TIMER),
+            scheduler: Scheduler::Clock,
+            frame_cycles: 0,
+            vblank_wait: false,
+            quirks: Quirks::new(),
+            rng: Rng::new(seed),
+            key_wait: None,
+            pattern: None,
+            pitch: 64,
         }
     }
     
-    pub fn tick (&mut self, time: f64, memory: &mut Memory, display: &mut Display, keypad: &Keypad) {
-        if self.clock.tick(time) {
-            self.cycle(memory, display, keypad);
-        }
+    pub fn tick (&mut self, time: f64, memory: &mut Memory, display: &mut Display, keypad: &Keypad, keypad2: Option<&Keypad>) -> Result<(), CpuError> {
+        match self.scheduler {
+            Scheduler::Clock => {
+                if self.clock.tick(time) && !self.vblank_wait {
+                    self.cycle(memory, display, keypad, keypad2)?;
+                }
+
+                if self.clock_timer.tick(time) {
+                    self.cycle_timers();
+                }
+            },
+            Scheduler::Ipf(ipf) => {
+                if !self.vblank_wait {
+                    self.cycle(memory, display, keypad, keypad2)?;
+                }
+
+                self.clock.cycles += 1;
+                self.frame_cycles += 1;
 
-        if self.clock_timer.tick(time) {
-            self.cycle_timers();
+                if self.frame_cycles >= ipf {
+                    self.frame_cycles = 0;
+                    self.clock_timer.cycles += 1;
+                    self.cycle_timers();
+                }
+            },
+            Scheduler::Vip => {
+                if !self.vblank_wait {
+                    let pc = self.pc;
+                    let vx = self.v[(memory.ram.get(pc as usize).copied().unwrap_or(0) & 0xF) as usize];
+                    let instruction = self.cycle(memory, display, keypad, keypad2)?;
+
+                    self.frame_cycles += vip_cycles(&instruction, vx, self.pc == pc + 4);
+                }
+
+                self.clock.cycles += 1;
+
+                if self.vblank_wait || self.frame_cycles >= VIP_FRAME_BUDGET {
+                    // Cycles overspent by a long instruction, even one that then waits for the interrupt, are taken from the next frame
+                    self.frame_cycles = self.frame_cycles.saturating_sub(VIP_FRAME_BUDGET);
+                    self.clock_timer.cycles += 1;
+                    self.cycle_timers();
+                }
+            },
         }
+
+        Ok(())
     }
 
-    pub fn cycle (&mut self, memory: &mut Memory, display: &mut Display, keypad: &Keypad) -> Instruction {
-        let instruction = memory.fetch(self.pc);
+    /**
+     * On error, PC is left on the faulty instruction.
+     * CHIP-8X instructions are only run when the display has color attributes and there is a second keypad.
+     */
+    pub fn cycle (&mut self, memory: &mut Memory, display: &mut Display, keypad: &Keypad, keypad2: Option<&Keypad>) -> Result<Instruction, CpuError> {
+        let instruction = memory.fetch(self.pc).ok_or(CpuError::PcOutOfBounds(self.pc))?;
 
         // Log state after fetch step
         self.log(&instruction);
@@ -53,12 +172,27 @@ impl Cpu {
 
         match instruction.nibbles {
             (0, 0, 0xE, 0) => display.clear(),
+            (0, 0x2, 0xA, 0) if display.colors.is_some() => {
+                if let Some(colors) = &mut display.colors {
+                    colors.step_background();
+                }
+            },
             (0, 0, 0xE, 0xE) => {
+                if self.sp == 0 {
+                    self.pc -= 2;
+                    return Err(CpuError::StackUnderflow(self.pc));
+                }
+
                 self.sp -= 1;
                 self.pc = self.stack[self.sp];
             },
             (0x1, _, _, _) => self.pc = instruction.nnn,
             (0x2, _, _, _) => {
+                if self.sp == self.stack.len() {
+                    self.pc -= 2;
+                    return Err(CpuError::StackOverflow(self.pc));
+                }
+
                 self.stack[self.sp] = self.pc;
                 self.sp += 1;
                 self.pc = instruction.nnn;
@@ -66,52 +200,101 @@ impl Cpu {
             (0x3, _, _, _) => self.pc += if self.v[instruction.x] == instruction.nn { 2 } else { 0 },
             (0x4, _, _, _) => self.pc += if self.v[instruction.x] != instruction.nn { 2 } else { 0 },
             (0x5, _, _, 0) => self.pc += if self.v[instruction.x] == self.v[instruction.y] { 2 } else { 0 },
+            // Each nibble is added on its own, without carry
+            (0x5, _, _, 0x1) if display.colors.is_some() => {
+                let (vx, vy) = (self.v[instruction.x], self.v[instruction.y]);
+                self.v[instruction.x] = (vx & 0xF0).wrapping_add(vy & 0xF0) | (((vx & 0x0F) + (vy & 0x0F)) & 0x0F);
+            },
             (0x6, _, _, _) => self.v[instruction.x] = instruction.nn,
             (0x7, _, _, _) => self.v[instruction.x] = self.v[instruction.x].wrapping_add(instruction.nn),
             (0x8, _, _, 0) => self.v[instruction.x] = self.v[instruction.y],
-            (0x8, _, _, 0x1) => self.v[instruction.x] |= self.v[instruction.y],
-            (0x8, _, _, 0x2) => self.v[instruction.x] &= self.v[instruction.y],
-            (0x8, _, _, 0x3) => self.v[instruction.x] ^= self.v[instruction.y],
+            (0x8, _, _, 0x1) => {
+                self.v[instruction.x] |= self.v[instruction.y];
+                self.reset_flag();
+            },
+            (0x8, _, _, 0x2) => {
+                self.v[instruction.x] &= self.v[instruction.y];
+                self.reset_flag();
+            },
+            (0x8, _, _, 0x3) => {
+                self.v[instruction.x] ^= self.v[instruction.y];
+                self.reset_flag();
+            },
             (0x8, _, _, 0x4) => {
                 let (res, overflow) = self.v[instruction.x].overflowing_add(self.v[instruction.y]);
-                self.v[0xF] = if overflow { 1 } else { 0 };
-                self.v[instruction.x] = res;
+                self.set_with_flag(instruction.x, res, overflow);
             },
             (0x8, _, _, 0x5) => {
                 let (res, overflow) = self.v[instruction.x].overflowing_sub(self.v[instruction.y]);
-                self.v[0xF] = if overflow { 0 } else { 1 };
-                self.v[instruction.x] = res;
+                self.set_with_flag(instruction.x, res, !overflow);
             },
             (0x8, _, _, 0x6) => {
-                self.v[0xF] = if (self.v[instruction.x] & 1) == 1 { 1 } else { 0 };
-                self.v[instruction.x] = self.v[instruction.x].wrapping_div(2);
+                let value = self.shift_source(&instruction);
+                self.set_with_flag(instruction.x, value.wrapping_div(2), (value & 1) == 1);
             },
             (0x8, _, _, 0x7) => {
                 let (res, overflow) = self.v[instruction.y].overflowing_sub(self.v[instruction.x]);
-                self.v[0xF] = if overflow { 0 } else { 1 };
-                self.v[instruction.x] = res;
+                self.set_with_flag(instruction.x, res, !overflow);
             },
             (0x8, _, _, 0xE) => {
-                self.v[0xF] = if (self.v[instruction.x] >> 7) == 1 { 1 } else { 0 };
-                self.v[instruction.x] = self.v[instruction.x].wrapping_mul(2);
+                let value = self.shift_source(&instruction);
+                self.set_with_flag(instruction.x, value.wrapping_mul(2), (value >> 7) == 1);
             },
             (0x9, _, _, 0) => self.pc += if self.v[instruction.x] != self.v[instruction.y] { 2 } else { 0 },
             (0xA, _, _, _) => self.i = instruction.nnn,
-            (0xB, _, _, _) => self.pc = instruction.nnn + self.v[0] as u16,
-            (0xC, _, _, _) => self.v[instruction.x] = ((Math::random() * 255.0) as u8 + 1) & instruction.nn,
+            (0xB, _, _, _) if display.colors.is_some() => {
+                let (vx, vy, color) = (self.v[instruction.x], self.v[instruction.y], self.v[(instruction.x + 1) & 0xF]);
+
+                if let Some(colors) = &mut display.colors {
+                    match instruction.n {
+                        0   =>  colors.fill_zones(vx, vy, color),
+                        n   =>  colors.fill_rows(vx, vy, n, color),
+                    }
+                }
+            },
+            (0xB, _, _, _) => self.pc = instruction.nnn + self.v[if self.quirks.jump { instruction.x } else { 0 }] as u16,
+            (0xC, _, _, _) => self.v[instruction.x] = self.rng.next_u8() & instruction.nn,
             (0xD, _, _, _) => {
-                let bytes = &memory.ram[self.i as usize .. self.i as usize + instruction.n as usize];
+                let bytes = self.memory_from_i(instruction.n as usize)?;
                 let collision = display.draw_sprite(
                     self.v[instruction.x] as usize,
                     self.v[instruction.y] as usize,
-                    bytes,
+                    &memory.ram[bytes],
+                    self.quirks.wrap,
                 );
 
                 self.v[0xF] = if collision { 1 } else { 0 };
+                // On the VIP, drawing waits for the display interrupt
+                self.vblank_wait = self.quirks.vblank || self.scheduler == Scheduler::Vip;
+            },
+            (0xE, _, 0x9, 0xE) => self.pc += if self.key(keypad, instruction.x)? { 2 } else { 0 },
+            (0xE, _, 0xA, 0x1) => self.pc += if self.key(keypad, instruction.x)? { 0 } else { 2 },
+            (0xE, _, 0xF, 0x2) | (0xE, _, 0xF, 0x5) if keypad2.is_some() => {
+                if let Some(keypad2) = keypad2 {
+                    let pressed = self.key(keypad2, instruction.x)?;
+
+                    self.pc += if pressed == (instruction.nn == 0xF2) { 2 } else { 0 };
+                }
+            },
+            (0xF, 0, 0, 0x2) => {
+                let mut pattern = [0; 16];
+                let bytes = self.memory_from_i(16)?;
+
+                pattern.copy_from_slice(&memory.ram[bytes]);
+                self.pattern = Some(pattern);
             },
-            (0xE, _, 0x9, 0xE) => self.pc += if keypad.state[self.v[instruction.x] as usize] { 2 } else { 0 },
-            (0xE, _, 0xA, 0x1) => self.pc += if keypad.state[self.v[instruction.x] as usize] { 0 } else { 2 },
             (0xF, _, 0, 0x7) => self.v[instruction.x] = self.dt,
+            (0xF, _, 0, 0xA) if self.quirks.key_release => {
+                // Wait for a key to be released since the instruction was first run, or loop back
+                let since = *self.key_wait.get_or_insert(keypad.sequence);
+
+                if let Some(key) = keypad.released_since(since) {
+                    self.v[instruction.x] = key;
+                    self.key_wait = None;
+                } else {
+                    self.pc -= 2;
+                }
+            },
             (0xF, _, 0, 0xA) => {
                 // Check for key press, or loop back
                 let n = keypad.state.iter().position(|&key| key);
@@ -129,33 +312,43 @@ impl Cpu {
                 self.st = self.v[instruction.x];
             },
             (0xF, _, 0x1, 0xE) => {
-                self.i += self.v[instruction.x] as u16;
+                self.i = self.i.wrapping_add(self.v[instruction.x] as u16);
             },
             (0xF, _, 0x2, 0x9) => {
                 self.i = (RESERVED_START + 5 * self.v[instruction.x] as usize) as u16;
             },
+            (0xF, _, 0x3, 0xA) => {
+                self.pitch = self.v[instruction.x];
+            },
             (0xF, _, 0x3, 0x3) => {
-                memory.ram[self.i as usize] = self.v[instruction.x] / 100 % 10;
-                memory.ram[self.i as usize + 1] = self.v[instruction.x] / 10 % 10;
-                memory.ram[self.i as usize + 2] = self.v[instruction.x] % 10;
+                let vx = self.v[instruction.x];
+
+                memory.ram[self.memory_from_i(3)?].copy_from_slice(&[vx / 100 % 10, vx / 10 % 10, vx % 10]);
             },
             (0xF, _, 0x5, 0x5) => {
-                for n in 0..instruction.x + 1 {
-                    memory.ram[self.i as usize + n as usize] = self.v[n as usize];
-                }
+                let bytes = self.memory_from_i(instruction.x + 1)?;
+
+                memory.ram[bytes].copy_from_slice(&self.v[..= instruction.x]);
+                self.increment_memory_index(&instruction);
             },
             (0xF, _, 0x6, 0x5) => {
-                for n in 0..instruction.x + 1 {
-                    self.v[n as usize] = memory.ram[self.i as usize + n as usize];
-                }
+                let bytes = self.memory_from_i(instruction.x + 1)?;
+
+                self.v[..= instruction.x].copy_from_slice(&memory.ram[bytes]);
+                self.increment_memory_index(&instruction);
+            },
+            (..) => {
+                self.pc -= 2;
+                return Err(CpuError::UnknownInstruction(self.pc, instruction.opcode));
             },
-            (..) => panic!("Unknown instruction {:02X}", instruction.opcode),
         }
 
-        instruction
+        Ok(instruction)
     }
 
     pub fn cycle_timers (&mut self) {
+        self.vblank_wait = false;
+
         if self.dt > 0 {
             self.dt -= 1;
         }
@@ -169,6 +362,59 @@ impl Cpu {
         self.st > 0
     }
 
+    /**
+     * Memory range of a length from I, or an error with PC left on the instruction if it goes past the end of memory
+     */
+    fn memory_from_i (&mut self, length: usize) -> Result<Range<usize>, CpuError> {
+        let start = self.i as usize;
+
+        if start + length <= MEMORY_SIZE {
+            Ok(start .. start + length)
+        } else {
+            self.pc -= 2;
+            Err(CpuError::MemoryOutOfBounds(self.pc, self.i))
+        }
+    }
+
+    /**
+     * State of the key in VX, or an error with PC left on the instruction if VX is not a key
+     */
+    fn key (&mut self, keypad: &Keypad, x: usize) -> Result<bool, CpuError> {
+        match keypad.state.get(self.v[x] as usize) {
+            Some(&state)    =>  Ok(state),
+            None            =>  {
+                self.pc -= 2;
+                Err(CpuError::KeyOutOfBounds(self.pc, self.v[x]))
+            },
+        }
+    }
+
+    fn reset_flag (&mut self) {
+        if self.quirks.logic {
+            self.v[0xF] = 0;
+        }
+    }
+
+    fn set_with_flag (&mut self, x: usize, value: u8, flag: bool) {
+        if self.quirks.flag_last {
+            self.v[x] = value;
+            self.v[0xF] = flag as u8;
+        } else {
+            self.v[0xF] = flag as u8;
+            self.v[x] = value;
+        }
+    }
+
+    fn shift_source (&self, instruction: &Instruction) -> u8 {
+        self.v[if self.quirks.shift { instruction.x } else { instruction.y }]
+    }
+
+    fn increment_memory_index (&mut self, instruction: &Instruction) {
+        if !self.quirks.memory_leave_i_unchanged {
+            self.i += instruction.x as u16 + if self.quirks.memory_increment_by_x { 0 } else { 1 };
+        }
+    }
+
     fn log (&self, instruction: &Instruction) {
         log::trace!(
             "{:04X} {:04X} {:16} {} I:{:03X} SP:{:02} DT:{:02} ST:{:02} CYC:{}",
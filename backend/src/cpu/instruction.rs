@@ -73,11 +73,13 @@ impl Instruction {
             disassembly: match nibbles {
                 (0, 0, 0xE, 0)      => format!("CLS"),
                 (0, 0, 0xE, 0xE)    => format!("RET"),
+                (0, 0x2, 0xA, 0)    => format!("BGCOL"),
                 (0x1, _, _, _)      => format!("JMP {:03X}", nnn),
                 (0x2, _, _, _)      => format!("CALL {:03X}", nnn),
                 (0x3, _, _, _)      => format!("SE V{:X}, {:02X}", x, nn),
                 (0x4, _, _, _)      => format!("SNE V{:X}, {:02X}", x, nn),
                 (0x5, _, _, 0)      => format!("SE V{:X}, V{:X}", x, y),
+                (0x5, _, _, 0x1)    => format!("ADDN V{:X}, V{:X}", x, y),
                 (0x6, _, _, _)      => format!("LD V{:X}, {:02X}", x, nn),
                 (0x7, _, _, _)      => format!("ADD V{:X}, {:02X}", x, nn),
                 (0x8, _, _, 0)      => format!("LD V{:X}, V{:X}", x, y),
@@ -96,12 +98,16 @@ impl Instruction {
                 (0xD, _, _, _)      => format!("DRW V{:X}, V{:X}, {:X}", x, y, n),
                 (0xE, _, 0x9, 0xE)  => format!("SKP V{:X}", x),
                 (0xE, _, 0xA, 0x1)  => format!("SKNP V{:X}", x),
+                (0xE, _, 0xF, 0x2)  => format!("SKP2 V{:X}", x),
+                (0xE, _, 0xF, 0x5)  => format!("SKNP2 V{:X}", x),
+                (0xF, 0, 0, 0x2)    => format!("LD AUDIO, [I]"),
                 (0xF, _, 0, 0x7)    => format!("LD V{:X}, DT", x),
                 (0xF, _, 0, 0xA)    => format!("LD V{:X}, KEY", x),
                 (0xF, _, 0x1, 0x5)  => format!("LD DT, V{:X}", x),
                 (0xF, _, 0x1, 0x8)  => format!("LD ST, V{:X}", x),
                 (0xF, _, 0x1, 0xE)  => format!("ADD I, V{:X}", x),
                 (0xF, _, 0x2, 0x9)  => format!("LD I, FONT(V{:X})", x),
+                (0xF, _, 0x3, 0xA)  => format!("LD PITCH, V{:X}", x),
                 (0xF, _, 0x3, 0x3)  => format!("BCD V{:X}", x),
                 (0xF, _, 0x5, 0x5)  => format!("LD [I], V{:X}", x),
                 (0xF, _, 0x6, 0x5)  => format!("LD V{:X}, [I]", x),
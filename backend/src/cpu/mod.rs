@@ -1,5 +1,7 @@
 mod cpu;
 mod instruction;
+mod timing;
 
 pub use cpu::*;
 pub use instruction::*;
+pub use timing::*;
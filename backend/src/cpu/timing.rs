@@ -0,0 +1,71 @@
+use crate::cpu::instruction::Instruction;
+
+/**
+ * CDP1802 clock of the COSMAC VIP. A machine cycle takes 8 clock pulses.
+ */
+pub const VIP_CLOCK: f64 = 1_760_640.0;
+
+/**
+ * Machine cycles between two CDP1861 interrupts (1760640 / 8 / 60)
+ */
+pub const VIP_FRAME_CYCLES: u32 = 3668;
+
+/**
+ * Machine cycles stolen each frame by display DMA (128 lines of 8 bytes)
+ */
+pub const VIP_DMA_CYCLES: u32 = 1024;
+
+/**
+ * Machine cycles left to the interpreter each frame
+ */
+pub const VIP_FRAME_BUDGET: u32 = VIP_FRAME_CYCLES - VIP_DMA_CYCLES;
+
+/**
+ * Machine cycles spent by the interpreter main loop fetching and dispatching an instruction
+ */
+const VIP_FETCH_CYCLES: u32 = 40;
+
+/**
+ * Machine cycles taken by the VIP interpreter to run an instruction, including fetch and dispatch.
+ * Costs follow the analysis of the original interpreter listing.
+ * https://www.laurencescotford.com/chip-8-on-the-cosmac-vip-index/
+ * `vx` is the value of VX before execution and `skipped` whether a skip instruction skipped.
+ */
+pub fn vip_cycles (instruction: &Instruction, vx: u8, skipped: bool) -> u32 {
+    let skip = if skipped { 4 } else { 0 };
+
+    VIP_FETCH_CYCLES + match instruction.nibbles {
+        (0, 0, 0xE, 0)      =>  3078,
+        (0, 0, 0xE, 0xE)    =>  10,
+        (0x1, _, _, _)      =>  12,
+        (0x2, _, _, _)      =>  26,
+        (0x3, _, _, _)      =>  10 + skip,
+        (0x4, _, _, _)      =>  10 + skip,
+        (0x5, _, _, _)      =>  18 + skip,
+        (0x6, _, _, _)      =>  6,
+        (0x7, _, _, _)      =>  10,
+        (0x8, _, _, _)      =>  44,
+        (0x9, _, _, _)      =>  18 + skip,
+        (0xA, _, _, _)      =>  12,
+        (0xB, _, _, _)      =>  22,
+        (0xC, _, _, _)      =>  36,
+        // Sprites not aligned on a byte boundary are shifted across two bytes
+        (0xD, _, _, _)      =>  26 + instruction.n as u32 * if vx & 7 == 0 { 46 } else { 66 },
+        (0xE, _, _, _)      =>  14 + skip,
+        (0xF, _, 0x1, 0xE)  =>  16,
+        (0xF, _, 0x2, 0x9)  =>  16,
+        (0xF, _, 0x3, 0x3)  =>  80 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10) as u32,
+        (0xF, _, 0x5, 0x5)  =>  14 + 14 * (instruction.x as u32 + 1),
+        (0xF, _, 0x6, 0x5)  =>  14 + 14 * (instruction.x as u32 + 1),
+        (0xF, _, _, _)      =>  10,
+        (..)                =>  0,
+    }
+}
+
+#[test]
+fn costs () {
+    assert_eq!(vip_cycles(&Instruction::new(0x6012), 0, false), 46);
+    assert_eq!(vip_cycles(&Instruction::new(0x3012), 0, true), 54);
+    assert_eq!(vip_cycles(&Instruction::new(0xD015), 3, false), 396);
+    assert_eq!(vip_cycles(&Instruction::new(0xF033), 255, false), 312);
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use wasm_bindgen::prelude::*;
+use crate::{
+    input::{Mapping, KEYS},
+    memory::RomInfo,
+    palette::Palette,
+    quirks::Quirks,
+    util::sha1::sha1_hex,
+    Emulator,
+};
+
+/**
+ * Bundled database, in the format of the community CHIP-8 database so its files can be used as they are.
+ * https://github.com/chip-8/chip-8-database
+ */
+const PLATFORMS: &str = include_str!("database/platforms.json");
+const PROGRAMS: &str = include_str!("database/programs.json");
+
+/**
+ * Platforms ROMs are written for, each with its own quirks and speed
+ */
+#[wasm_bindgen]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Platform {
+    /**
+     * Original interpreter on the COSMAC VIP
+     */
+    CosmacVip,
+
+    /**
+     * CHIP-8 as most modern interpreters run it
+     */
+    ModernChip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 4] = [Platform::CosmacVip, Platform::ModernChip8, Platform::SuperChip, Platform::XoChip];
+
+    /**
+     * Identifier in the database. Other platforms of the database are not supported.
+     */
+    pub fn id (&self) -> &'static str {
+        match self {
+            Platform::CosmacVip     =>  "originalChip8",
+            Platform::ModernChip8   =>  "modernChip8",
+            Platform::SuperChip     =>  "superchip",
+            Platform::XoChip        =>  "xochip",
+        }
+    }
+
+    pub fn from_id (id: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|platform| platform.id() == id)
+    }
+}
+
+#[derive(serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformProfile {
+    pub id: String,
+    pub name: String,
+
+    /**
+     * Instructions per frame
+     */
+    pub default_tickrate: u32,
+    pub quirks: Quirks,
+}
+
+#[derive(serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct RomColors {
+    /**
+     * `#RRGGBB` colors for the background, the first plane, the second plane and both planes
+     */
+    #[serde(default)]
+    pub pixels: Vec<String>,
+}
+
+/**
+ * Settings of a ROM file. Settings left out come from its platform.
+ */
+#[derive(serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Rom {
+    /**
+     * Platforms the ROM runs on, preferred first
+     */
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    pub tickrate: Option<u32>,
+    pub start_address: Option<usize>,
+
+    /**
+     * Keypad keys for `up`, `down`, `left`, `right`, `a` and `b`
+     */
+    #[serde(default)]
+    pub keys: HashMap<String, u8>,
+    pub colors: Option<RomColors>,
+
+    /**
+     * Quirks that differ from those of a platform, by platform
+     */
+    #[serde(default)]
+    pub quirky_platforms: HashMap<String, serde_json::Value>,
+}
+
+#[derive(serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct Program {
+    pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+
+    /**
+     * Versions of the program by SHA-1
+     */
+    pub roms: HashMap<String, Rom>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Database {
+    pub platforms: Vec<PlatformProfile>,
+    pub programs: Vec<Program>,
+
+    /**
+     * Program of each ROM by SHA-1
+     */
+    index: HashMap<String, usize>,
+}
+
+impl Database {
+    pub fn parse (platforms: &str, programs: &str) -> Result<Self, serde_json::Error> {
+        let mut database = Self {
+            platforms: serde_json::from_str(platforms)?,
+            programs: Vec::new(),
+            index: HashMap::new(),
+        };
+
+        database.import(programs)?;
+        Ok(database)
+    }
+
+    /**
+     * Adds programs, replacing known ROMs. Returns the number of ROMs added.
+     */
+    pub fn import (&mut self, programs: &str) -> Result<usize, serde_json::Error> {
+        let programs: Vec<Program> = serde_json::from_str(programs)?;
+        let mut count = 0;
+
+        for program in programs {
+            for hash in program.roms.keys() {
+                self.index.insert(hash.to_lowercase(), self.programs.len());
+                count += 1;
+            }
+
+            self.programs.push(program);
+        }
+
+        Ok(count)
+    }
+
+    pub fn find (&self, rom: &[u8]) -> Option<(&Program, &Rom)> {
+        let hash = sha1_hex(rom);
+        let program = &self.programs[*self.index.get(&hash)?];
+
+        program.roms.iter().find(|(sha1, _)| sha1.to_lowercase() == hash).map(|(_, rom)| (program, rom))
+    }
+
+    pub fn profile (&self, platform: Platform) -> Option<&PlatformProfile> {
+        self.platforms.iter().find(|profile| profile.id == platform.id())
+    }
+}
+
+/**
+ * Bundled database, parsed once, along with imported programs.
+ * It is shared by the whole process, since ROMs are looked up by `Emulator.new` before any emulator exists.
+ */
+fn database () -> &'static RwLock<Database> {
+    static DATABASE: OnceLock<RwLock<Database>> = OnceLock::new();
+
+    // Checked by the `bundled` test
+    DATABASE.get_or_init(|| RwLock::new(Database::parse(PLATFORMS, PROGRAMS).unwrap()))
+}
+
+/**
+ * Applies the quirks a ROM changes over those of its platform
+ */
+fn quirks_override (quirks: Quirks, changes: &serde_json::Value) -> Quirks {
+    let mut value = serde_json::to_value(quirks).unwrap();
+
+    if let (Some(value), Some(changes)) = (value.as_object_mut(), changes.as_object()) {
+        value.extend(changes.clone());
+    }
+
+    serde_json::from_value(value).unwrap_or(quirks)
+}
+
+/**
+ * Host input for the abstract buttons of the database: directions on the arrow keys, A and B on Z and X
+ */
+fn key_input (button: &str) -> Option<&'static str> {
+    match button {
+        "up"    =>  Some("arrowup"),
+        "down"  =>  Some("arrowdown"),
+        "left"  =>  Some("arrowleft"),
+        "right" =>  Some("arrowright"),
+        "a"     =>  Some("z"),
+        "b"     =>  Some("x"),
+        _       =>  None,
+    }
+}
+
+fn color (hex: &str) -> Option<u32> {
+    u32::from_str_radix(hex.trim_start_matches('#'), 16).ok()
+}
+
+impl Emulator {
+    /**
+     * Applies the platform, quirks, speed, load address, key mapping and colors the database has for the ROM, if it knows it.
+     * Mappings and palettes set for the ROM by the host are kept.
+     */
+    pub (crate) fn apply_database (&mut self, database: &Database) {
+        let (program, rom) = match database.find(&self.rom) {
+            Some((program, rom))    =>  (program.clone(), rom.clone()),
+            None                    =>  return,
+        };
+        let platform = rom.platforms.iter().find_map(|id| Platform::from_id(id));
+
+        log::info!("Detected {} ({:?})", program.title, platform);
+
+        if let Some(platform) = platform {
+            self.apply_platform(database, platform);
+
+            if let Some(changes) = rom.quirky_platforms.get(platform.id()) {
+                self.cpu.quirks = quirks_override(self.cpu.quirks, changes);
+            }
+
+            // The VIP runs at the speed of its own interpreter
+            if let (Some(tickrate), false) = (rom.tickrate, platform == Platform::CosmacVip) {
+                self.set_instructions_per_frame(tickrate);
+            }
+        }
+
+        if let Some(address) = rom.start_address.filter(|&address| RomInfo::new(&self.rom, address).is_ok()) {
+            self.load_address = address;
+            self.reset();
+        }
+
+        let bindings: HashMap<String, u8> = rom.keys
+            .iter()
+            .filter(|(_, &key)| (key as usize) < KEYS)
+            .filter_map(|(button, &key)| key_input(button).map(|input| (input.to_string(), key)))
+            .collect();
+
+        if !bindings.is_empty() {
+            self.input.config.roms.entry(self.input.rom.clone()).or_insert(Mapping { bindings });
+        }
+
+        let colors = rom.colors.iter().flat_map(|colors| colors.pixels.iter()).filter_map(|hex| color(hex)).collect::<Vec<u32>>();
+
+        if colors.len() >= 2 {
+            let default = self.palettes.default;
+            let palette = Palette::new(colors[0], colors[1], colors.get(2).copied().unwrap_or(default.plane2), colors.get(3).copied().unwrap_or(default.blend));
+
+            self.palettes.overrides.entry(sha1_hex(&self.rom)).or_insert(palette);
+            self.update_palette();
+        }
+
+        self.program = Some(program);
+    }
+
+    /**
+     * The COSMAC VIP uses VIP timing rather than a fixed tickrate, as it reproduces the speed of its interpreter instruction by instruction
+     */
+    fn apply_platform (&mut self, database: &Database, platform: Platform) {
+        if let Some(profile) = database.profile(platform) {
+            self.cpu.quirks = profile.quirks;
+
+            match platform {
+                Platform::CosmacVip =>  self.set_vip_timing(),
+                _                   =>  self.set_instructions_per_frame(profile.default_tickrate),
+            }
+        }
+
+        self.platform = Some(platform);
+    }
+
+    /**
+     * Looks the ROM up in the bundled and imported programs
+     */
+    pub (crate) fn detect (&mut self) {
+        // The lock is only poisoned if a panic happened while importing, which aborts in wasm
+        self.apply_database(&database().read().unwrap());
+    }
+}
+
+/**
+ * Adds programs in the format of the community database's `programs.json`, to be detected by `Emulator.new`.
+ * The database is global: imported programs stay for the lifetime of the wasm module and apply to every emulator created afterwards, while emulators already created keep their settings.
+ * Returns the number of ROMs added.
+ */
+#[wasm_bindgen]
+pub fn database_import (programs: &str) -> Result<usize, JsValue> {
+    database().write().unwrap().import(programs).map_err(|err| JsValue::from_str(&err.to_string()))
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Platform the ROM was detected as or set to, if any
+     */
+    pub fn platform (&self) -> Option<Platform> {
+        self.platform
+    }
+
+    /**
+     * Overrides detection, applying the quirks and speed of another platform
+     */
+    pub fn set_platform (&mut self, platform: Platform) {
+        self.apply_platform(&database().read().unwrap(), platform);
+    }
+
+    /**
+     * Title of the ROM, if it is in the database
+     */
+    pub fn rom_title (&self) -> Option<String> {
+        self.program.as_ref().map(|program| program.title.clone())
+    }
+}
+
+#[test]
+fn bundled () {
+    let database = Database::parse(PLATFORMS, PROGRAMS).unwrap();
+
+    for platform in Platform::ALL {
+        assert!(database.profile(platform).is_some());
+    }
+}
+
+#[test]
+fn detection () {
+    // ROMs only used by this test, as imports are shared by all emulators
+    let (vip, modern) = ([0x12, 0x00, 0xDB, 0x01], [0x12, 0x00, 0xDB, 0x02]);
+    let programs = format!(r##"[
+        {{
+            "title": "Loop",
+            "authors": ["Nobody"],
+            "release": "2024",
+            "roms": {{
+                "{}": {{
+                    "file": "loop-vip.ch8",
+                    "platforms": ["hybridVIP", "originalChip8"],
+                    "tickrate": 20,
+                    "keys": {{ "up": 5, "a": 6, "player2Up": 1 }},
+                    "colors": {{ "pixels": ["#000001", "#000002"], "buzzer": "#FFAA00" }}
+                }},
+                "{}": {{
+                    "file": "loop.ch8",
+                    "platforms": ["modernChip8"],
+                    "tickrate": 20,
+                    "startAddress": 1536,
+                    "quirkyPlatforms": {{ "modernChip8": {{ "wrap": true }} }}
+                }}
+            }}
+        }}
+    ]"##, sha1_hex(&vip).to_uppercase(), sha1_hex(&modern));
+
+    assert_eq!(database_import(&programs).unwrap(), 2);
+
+    let mut emulator = Emulator::new(&vip).unwrap();
+    assert_eq!(emulator.platform(), Some(Platform::CosmacVip));
+    assert_eq!(emulator.rom_title().as_deref(), Some("Loop"));
+    assert!(emulator.quirks().vblank && emulator.quirks().key_release);
+    assert!(emulator.vip_timing());
+    let classic = Palette::theme(crate::palette::Theme::Classic);
+    assert_eq!(emulator.palette(), Palette::new(1, 2, classic.plane2, classic.blend));
+    assert_eq!(emulator.input.mapping().get("ArrowUp"), Some(5));
+    assert_eq!(emulator.input.mapping().get("z"), Some(6));
+
+    emulator.set_platform(Platform::SuperChip);
+    assert!(emulator.quirks().jump);
+    assert_eq!(emulator.instructions_per_frame(), Some(30));
+
+    let emulator = Emulator::new(&modern).unwrap();
+    assert_eq!(emulator.platform(), Some(Platform::ModernChip8));
+    assert!(emulator.quirks().wrap && !emulator.quirks().shift);
+    assert_eq!(emulator.instructions_per_frame(), Some(20));
+    assert_eq!(emulator.rom_info().address, 0x600);
+    assert!(Emulator::with_cpu_rate(&modern, 500.0).unwrap().platform().is_none());
+}
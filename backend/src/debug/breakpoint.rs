@@ -0,0 +1,19 @@
+use wasm_bindgen::prelude::*;
+use crate::Emulator;
+
+#[wasm_bindgen]
+impl Emulator {
+    pub fn debug_breakpoint_add (&mut self, address: u16) {
+        if !self.breakpoints.contains(&address) {
+            self.breakpoints.push(address);
+        }
+    }
+
+    pub fn debug_breakpoint_remove (&mut self, address: u16) {
+        self.breakpoints.retain(|&breakpoint| breakpoint != address);
+    }
+
+    pub fn debug_breakpoints (&self) -> Vec<u16> {
+        self.breakpoints.clone()
+    }
+}
@@ -20,11 +20,14 @@ impl Disassembly {
 
 #[wasm_bindgen]
 impl Emulator {
+    /**
+     * Null past the end of memory
+     */
     pub fn debug_disassembly_at (&mut self, address: u16) -> JsValue {
-        let instruction = self.memory.fetch(address);
-        let disassembly = Disassembly::new(instruction, address);
-
-        JsValue::from_serde(&disassembly).unwrap()
+        match self.memory.fetch(address) {
+            Some(instruction)   =>  JsValue::from_serde(&Disassembly::new(instruction, address)).unwrap(),
+            None                =>  JsValue::NULL,
+        }
     }
 
     pub fn debug_disassembly_index_to_address (&mut self, offset: u16) -> u16 {
@@ -6,4 +6,11 @@ impl Emulator {
     pub fn debug_input (&self) -> JsValue {
         JsValue::from_serde(&self.keypad.state).unwrap()
     }
+
+    /**
+     * Latest key press and release edges, oldest first
+     */
+    pub fn debug_input_events (&self) -> JsValue {
+        JsValue::from_serde(&self.keypad.events).unwrap()
+    }
 }
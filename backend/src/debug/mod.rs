@@ -1,3 +1,4 @@
+mod breakpoint;
 mod clock;
 mod cpu;
 mod disassembly;
@@ -21,6 +21,8 @@ pub const FONT_SET: [u8; 80] = [
   0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+use crate::chip8x::Colors;
+
 fn at (x: usize, y: usize) -> usize {
     (x + y * DISPLAY_WIDTH) % (DISPLAY_WIDTH * DISPLAY_HEIGHT)
 }
@@ -30,12 +32,18 @@ pub struct Display {
      * Framebuffer should be written to memory at addresses 0xF00 - 0xFFF
      */
     pub framebuffer: [bool; VRAM_SIZE],
+
+    /**
+     * Color attribute memory, on CHIP-8X
+     */
+    pub colors: Option<Colors>,
 }
 
 impl Display {
     pub fn new () -> Self {
         Self {
             framebuffer: [false; VRAM_SIZE], 
+            colors: None,
         }
     }
 
@@ -44,13 +52,18 @@ impl Display {
     }
 
     /**
-     * Sprites are up to 15 bytes (dimensions: 8x15)
+     * Sprites are up to 15 bytes (dimensions: 8x15).
+     * When not wrapping, only the starting position wraps and pixels past the edges are clipped
      */
-    pub fn draw_sprite (&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+    pub fn draw_sprite (&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool {
         let mut collision = false;
 
         for row in 0..sprite.len() {
             for column in 0..8 {
+                if !wrap && (x % DISPLAY_WIDTH + column >= DISPLAY_WIDTH || y % DISPLAY_HEIGHT + row >= DISPLAY_HEIGHT) {
+                    continue;
+                }
+
                 let (x_actual, y_actual) = ((x + column) % DISPLAY_WIDTH, (y + row) % DISPLAY_HEIGHT);
 
                 let old = self.framebuffer[at(x_actual, y_actual)];
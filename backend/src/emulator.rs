@@ -1,69 +1,739 @@
 use wasm_bindgen::prelude::*;
 use crate::{
+    chip8x::Colors,
     display::Display,
-    memory::Memory,
-    cpu::Cpu,
-    input::Keypad,
-    clock::Clock,
+    memory::{Memory, RomError, RomInfo, PROGRAM_START},
+    cpu::{Cpu, VIP_CLOCK},
+    input::{Keypad, InputError, InputMapper, Macros},
+    clock::{Clock, Scheduler, CLOCK_CPU, CLOCK_TIMER},
+    cheat::{Cheats, CheatTiming},
+    movie::MovieState,
+    quirks::Quirks,
+    rng::{self, Rng},
+    vip::Vip,
+    turbo::{Turbo, TurboAudio},
+    output::{self, Output, PixelFormat},
+    palette::Palettes,
+    recording::Recording,
+    audio::{Audio, Tone},
+    database::{Platform, Program},
 };
 
+/**
+ * Whether the emulator advances when the host asks for a frame
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RunState {
+    Running,
+    Paused,
+
+    /**
+     * The CPU faulted, only a reset resumes execution
+     */
+    Error,
+
+    /**
+     * PC reached a breakpoint
+     */
+    Breakpoint,
+}
+
 #[wasm_bindgen]
 pub struct Emulator {
     pub (crate) cpu: Cpu,
     pub (crate) memory: Memory,
     pub (crate) display: Display,
     pub (crate) keypad: Keypad,
+
+    /**
+     * Second keypad, which is only there on CHIP-8X
+     */
+    pub (crate) keypad2: Option<Keypad>,
+    pub (crate) input: InputMapper,
+    pub (crate) macros: Macros,
     pub (crate) clock: Clock,
+    pub (crate) cheats: Cheats,
+    pub (crate) movie: MovieState,
+    pub (crate) rom: Vec<u8>,
+
+    /**
+     * Address the ROM is loaded at, and where execution starts
+     */
+    pub (crate) load_address: usize,
+    pub (crate) seed: u64,
+    pub (crate) state: RunState,
+    pub (crate) error: Option<String>,
+    pub (crate) breakpoints: Vec<u16>,
+
+    /**
+     * Low-level machine running the ROM instead of the CPU, if any
+     */
+    pub (crate) vip: Option<Box<Vip>>,
+    pub (crate) turbo: Option<Turbo>,
+
+    /**
+     * Host frames run since the last presented one
+     */
+    pub (crate) turbo_frames: usize,
+    pub (crate) output: Output,
+    pub (crate) palettes: Palettes,
+
+    /**
+     * Gameplay being recorded to video, if any
+     */
+    pub (crate) video: Option<Recording>,
+
+    /**
+     * Beeper synthesis, if the host asked for samples
+     */
+    pub (crate) audio: Option<Audio>,
+
+    /**
+     * Platform whose quirks and speed were applied, and the database entry of the ROM, if detected
+     */
+    pub (crate) platform: Option<Platform>,
+    pub (crate) program: Option<Program>,
+}
+
+/**
+ * Outcome of a frame run with `run_frame`
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct FrameStatus {
+    /**
+     * Whether the framebuffer was updated, which is not the case for frames skipped in turbo
+     */
+    pub presented: bool,
+    pub beep: bool,
+    pub frame: usize,
+
+    /**
+     * Tells why the emulator halted, if it did
+     */
+    pub state: RunState,
 }
 
 #[wasm_bindgen]
 impl Emulator {
-    pub fn new (rom: &[u8]) -> Self {
-        Self {
-            cpu: Cpu::new(),
-            memory: Memory::new(rom),
+    /**
+     * Fails if the ROM is empty or does not fit in memory.
+     * ROMs found in the bundled database get the quirks, speed and colors of their platform.
+     */
+    pub fn new (rom: &[u8]) -> Result<Emulator, RomError> {
+        let mut emulator = Self::with_cpu_rate(rom, CLOCK_CPU)?;
+
+        emulator.detect();
+        Ok(emulator)
+    }
+
+    /**
+     * Timers always run at 60Hz, whatever the CPU rate.
+     * Skips the ROM database, so the default quirks and the given rate are used as is.
+     */
+    pub fn with_cpu_rate (rom: &[u8], rate: f64) -> Result<Emulator, RomError> {
+        let seed = rng::entropy();
+        let memory = Memory::new(rom, PROGRAM_START)?;
+        let mut emulator = Self {
+            cpu: Cpu::new(seed),
+            memory,
             display: Display::new(),
             keypad: Keypad::new(),
-            clock: Clock::new(crate::clock::CLOCK_CPU),
+            keypad2: None,
+            input: InputMapper::new(rom),
+            macros: Macros::new(),
+            clock: Clock::new(CLOCK_CPU),
+            cheats: Cheats::new(),
+            movie: MovieState::Idle,
+            rom: rom.to_vec(),
+            load_address: PROGRAM_START,
+            seed,
+            state: RunState::Running,
+            error: None,
+            breakpoints: Vec::new(),
+            vip: None,
+            turbo: None,
+            turbo_frames: 0,
+            output: Output::new(),
+            palettes: Palettes::new(),
+            video: None,
+            audio: None,
+            platform: None,
+            program: None,
+        };
+
+        emulator.set_cpu_rate(rate);
+        Ok(emulator)
+    }
+
+    /**
+     * Restarts the ROM from power-on, keeping quirks, seed, cheats and breakpoints.
+     * Clears halts, but stays paused if it was. Ends movie recording and playback.
+     */
+    pub fn reset (&mut self) {
+        self.movie_reset();
+
+        let (quirks, rate, scheduler) = (self.cpu.quirks, self.cpu.clock.rate, self.cpu.scheduler);
+
+        self.cpu = Cpu::new(self.seed);
+        self.cpu.quirks = quirks;
+        self.cpu.pc = self.load_address as u16;
+        // The ROM was checked against the load address when set
+        self.memory = Memory::new(&self.rom, self.load_address).unwrap();
+        self.display = Display::new();
+
+        if let Some(vip) = &mut self.vip {
+            vip.reset(&mut self.memory);
+        }
+
+        self.keypad = Keypad::new();
+
+        if self.keypad2.is_some() {
+            self.keypad2 = Some(Keypad::new());
+            self.display.colors = Some(Colors::new());
+        }
+        self.clock = Clock::new(CLOCK_CPU);
+
+        if let Some(audio) = &mut self.audio {
+            audio.rebase(0.0);
+        }
+
+        self.set_scheduler(rate, scheduler);
+        self.error = None;
+
+        if self.state != RunState::Paused {
+            self.state = RunState::Running;
         }
     }
 
-    pub fn cycle (&mut self) {
-        self.cpu.tick(
-            self.clock.time,
-            &mut self.memory,
-            &mut self.display,
-            &self.keypad,
-        );
+    /**
+     * Size of the ROM and how it fits in memory
+     */
+    pub fn rom_info (&self) -> RomInfo {
+        // The ROM was checked against the load address when set
+        RomInfo::new(&self.rom, self.load_address).unwrap()
+    }
 
-        self.clock.tick();
+    /**
+     * Loads the ROM at another address, such as `PROGRAM_START_ETI` for ETI-660 programs, and restarts it
+     */
+    pub fn set_load_address (&mut self, address: usize) -> Result<(), RomError> {
+        RomInfo::new(&self.rom, address)?;
+        self.load_address = address;
+        self.reset();
+        Ok(())
     }
 
-    pub fn cycle_until_timer (&mut self) {
-        let cycle = self.cpu.clock_timer.cycles;
-        
-        while cycle == self.cpu.clock_timer.cycles {
-            self.cycle();
+    pub fn state (&self) -> RunState {
+        self.state
+    }
+
+    /**
+     * Message of the fault that halted the CPU
+     */
+    pub fn error (&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    pub fn pause (&mut self) {
+        if self.state == RunState::Running {
+            self.state = RunState::Paused;
         }
     }
 
+    pub fn resume (&mut self) {
+        if self.state == RunState::Paused || self.state == RunState::Breakpoint {
+            self.state = RunState::Running;
+        }
+    }
+
+    /**
+     * Runs one 60Hz timer period if running, or several when in turbo. Meant to be called by the host on every repaint.
+     * Returns whether the framebuffer should be presented.
+     */
+    pub fn frame (&mut self) -> bool {
+        if self.state != RunState::Running {
+            return false;
+        }
+
+        for _ in 0..self.turbo_speed() {
+            self.cycle_until_timer();
+
+            if self.state != RunState::Running {
+                break;
+            }
+        }
+
+        self.turbo_present()
+    }
+
+    /**
+     * Runs a frame like `frame` does, and renders the framebuffer at `framebuffer_ptr` if it should be presented.
+     * Saves the host from several calls per frame.
+     */
+    pub fn run_frame (&mut self) -> FrameStatus {
+        let presented = self.frame();
+
+        if presented {
+            self.output.render(&self.display);
+        }
+
+        FrameStatus {
+            presented,
+            beep: self.beep(),
+            frame: self.frame_count(),
+            state: self.state,
+        }
+    }
+
+    /**
+     * Renders the framebuffer at `framebuffer_ptr`, when not running frames with `run_frame`
+     */
+    pub fn refresh_framebuffer (&mut self) {
+        self.output.render(&self.display);
+    }
+
+    /**
+     * Address of the framebuffer in wasm memory. It stays valid until the pixel format changes.
+     */
+    pub fn framebuffer_ptr (&self) -> *const u8 {
+        self.output.buffer.as_ptr()
+    }
+
+    /**
+     * Size of the framebuffer in bytes
+     */
+    pub fn framebuffer_len (&self) -> usize {
+        self.output.buffer.len()
+    }
+
+    pub fn pixel_format (&self) -> PixelFormat {
+        self.output.format
+    }
+
+    /**
+     * Reallocates the framebuffer in another format, so its pointer must be read again
+     */
+    pub fn set_pixel_format (&mut self, format: PixelFormat) {
+        self.output.set_format(format);
+        self.output.render(&self.display);
+    }
+
+    /**
+     * Runs one 60Hz timer period while paused or halted on a breakpoint, and stays paused.
+     * Stops early if another breakpoint is hit.
+     */
+    pub fn frame_advance (&mut self) {
+        if self.state == RunState::Paused || self.state == RunState::Breakpoint {
+            self.state = RunState::Paused;
+            self.cycle_until_timer();
+        }
+    }
+
+    pub fn cycle (&mut self) {
+        self.step();
+    }
+
+    /**
+     * Stops early on halt
+     */
+    pub fn cycle_until_timer (&mut self) {
+        let frame = self.frame_count();
+
+        while frame == self.frame_count() && self.step() {}
+    }
+
+    /**
+     * Stops early on halt
+     */
     pub fn cycle_until_cpu (&mut self) {
-        let cycle = self.cpu.clock.cycles;
-        
-        while cycle == self.cpu.clock.cycles {
-            self.cycle();
+        let cycle = self.cycle_count();
+
+        while cycle == self.cycle_count() && self.step() {}
+    }
+
+    /**
+     * Runs one clock tick, and returns whether execution may continue
+     */
+    fn step (&mut self) -> bool {
+        if self.state == RunState::Error {
+            return false;
         }
+
+        let (frame, cycle) = (self.frame_count(), self.cycle_count());
+        let tone = if self.audio.is_some() || self.video.is_some() { self.tone() } else { Tone::Silent };
+        let result = match &mut self.vip {
+            Some(vip) => {
+                vip.step(&mut self.memory, &mut self.display, &self.keypad);
+                Ok(())
+            },
+            None => self.cpu.tick(
+                self.clock.time,
+                &mut self.memory,
+                &mut self.display,
+                &self.keypad,
+                self.keypad2.as_ref(),
+            ),
+        };
+
+        if let Err(err) = result {
+            log::error!("{}", err);
+            self.state = RunState::Error;
+            self.error = Some(err.to_string());
+            return false;
+        }
+
+        self.clock.tick();
+        self.audio_advance(tone);
+        self.movie_playback();
+
+        if self.cheats.timing == CheatTiming::Cycle || frame != self.frame_count() {
+            self.cheats.apply(&mut self.memory);
+        }
+
+        if frame != self.frame_count() {
+            if let Some(phosphor) = &mut self.output.phosphor {
+                phosphor.update(&self.display);
+            }
+
+            self.video_capture();
+            self.macros_frame();
+        }
+
+        if self.vip.is_none() && cycle != self.cycle_count() && self.breakpoints.contains(&self.cpu.pc) {
+            self.state = RunState::Breakpoint;
+            return false;
+        }
+
+        true
     }
 
     pub fn beep (&self) -> bool {
-        self.cpu.beep()
+        if let Some(Turbo { audio: TurboAudio::Mute, .. }) = self.turbo {
+            return false;
+        }
+
+        match &self.vip {
+            Some(vip)   =>  vip.beep(),
+            None        =>  self.cpu.beep(),
+        }
+    }
+
+    /**
+     * Live input is ignored while a movie is playing
+     */
+    pub fn update_key (&mut self, key: usize, state: bool) -> Result<(), InputError> {
+        if self.movie_playing() {
+            return Ok(());
+        }
+
+        self.keypad.set(key, state, self.frame_count(), self.cycle_count())?;
+        self.movie_input(key, state, false);
+        Ok(())
+    }
+
+    /**
+     * Number of 60Hz timer periods elapsed since power-on
+     */
+    pub fn frame_count (&self) -> usize {
+        match &self.vip {
+            Some(vip)   =>  vip.frames,
+            None        =>  self.cpu.clock_timer.cycles,
+        }
+    }
+
+    /**
+     * Number of CPU cycles elapsed since power-on
+     */
+    pub fn cycle_count (&self) -> usize {
+        match &self.vip {
+            Some(vip)   =>  vip.cycles,
+            None        =>  self.cpu.clock.cycles,
+        }
+    }
+
+    pub fn cpu_rate (&self) -> f64 {
+        self.cpu.clock.rate
+    }
+
+    /**
+     * Changes the CPU frequency in Hz, taking effect on the next cycle.
+     * Switches back to clock scheduling if instructions per frame were set.
+     */
+    pub fn set_cpu_rate (&mut self, rate: f64) {
+        if !(rate.is_finite() && rate > 0.0) {
+            log::warn!("Ignoring invalid CPU rate {}", rate);
+            return;
+        }
+
+        self.set_scheduler(rate, Scheduler::Clock);
+    }
+
+    pub fn instructions_per_frame (&self) -> Option<u32> {
+        match self.cpu.scheduler {
+            Scheduler::Ipf(ipf) =>  Some(ipf),
+            _                   =>  None,
+        }
+    }
+
+    /**
+     * Runs exactly this many instructions between timer decrements, instead of dividing a CPU frequency
+     */
+    pub fn set_instructions_per_frame (&mut self, ipf: u32) {
+        if ipf == 0 {
+            log::warn!("Ignoring invalid instructions per frame {}", ipf);
+            return;
+        }
+
+        self.set_scheduler(ipf as f64 * CLOCK_TIMER, Scheduler::Ipf(ipf));
+    }
+
+    pub fn vip_timing (&self) -> bool {
+        self.cpu.scheduler == Scheduler::Vip
+    }
+
+    /**
+     * Runs instructions at the speed of the original COSMAC VIP interpreter.
+     * Only the master clock runs at the VIP machine cycle rate, `cpu_rate` keeps reporting the CHIP-8 instruction rate.
+     */
+    pub fn set_vip_timing (&mut self) {
+        self.set_scheduler(self.cpu.clock.rate, Scheduler::Vip);
+    }
+
+    pub fn quirks (&self) -> Quirks {
+        self.cpu.quirks
     }
 
-    pub fn update_key (&mut self, key: usize, state: bool) {
-        self.keypad.state[key] = state;
+    pub fn set_quirks (&mut self, quirks: Quirks) {
+        self.cpu.quirks = quirks;
+        self.movie_quirks();
     }
 
+    pub fn seed (&self) -> u64 {
+        self.seed
+    }
+
+    /**
+     * Reseeds the random number generator used by CXNN
+     */
+    pub fn set_seed (&mut self, seed: u64) {
+        self.seed = seed;
+        self.cpu.rng = Rng::new(seed);
+    }
+
+    /**
+     * Copy of the framebuffer in RGBA, whatever the pixel format
+     */
     pub fn get_framebuffer (&self) -> Vec<u8> {
-        self.display.framebuffer.iter().flat_map(|&pixel| if pixel { [255, 255, 255, 255] } else { [0, 0, 0, 255] }).collect()
+        let mut buffer = vec![0; PixelFormat::Rgba8888.buffer_size(self.display.framebuffer.len())];
+
+        output::render_rgba(&self.display, &self.output.palette, self.output.phosphor.as_ref(), &mut buffer);
+        buffer
+    }
+}
+
+impl Emulator {
+    /**
+     * Changes speed for the speed setters, a reset or a movie. The next frame starts with a full budget of cycles.
+     */
+    pub (crate) fn set_scheduler (&mut self, rate: f64, scheduler: Scheduler) {
+        if scheduler == Scheduler::Clock && self.cpu.scheduler != Scheduler::Clock {
+            self.cpu.clock_timer.set_rate(CLOCK_TIMER, self.clock.time);
+        }
+
+        self.clock.rate = match scheduler {
+            // The master clock must tick at least as often as timers do
+            Scheduler::Clock    =>  rate.max(CLOCK_TIMER),
+            Scheduler::Ipf(_)   =>  rate,
+            Scheduler::Vip      =>  VIP_CLOCK / 8.0,
+        };
+        self.cpu.scheduler = scheduler;
+        self.cpu.clock.set_rate(rate, self.clock.time);
+        self.cpu.frame_cycles = 0;
+        self.movie_speed();
+    }
+}
+
+#[test]
+fn halt () {
+    let mut emulator = Emulator::new(&[0x60, 0x01, 0x12, 0x04, 0xFF, 0xFF]).unwrap();
+
+    emulator.debug_breakpoint_add(0x204);
+    emulator.frame();
+    assert_eq!(emulator.state(), RunState::Breakpoint);
+    assert_eq!(emulator.cpu.pc, 0x204);
+
+    emulator.resume();
+    emulator.frame();
+    assert_eq!(emulator.state(), RunState::Error);
+    assert_eq!(emulator.cpu.pc, 0x204);
+    assert_eq!(emulator.error(), Some(String::from("Unknown instruction FFFF at 204")));
+
+    emulator.reset();
+    assert_eq!(emulator.state(), RunState::Running);
+}
+
+#[test]
+fn errors () {
+    assert_eq!(Emulator::new(&[]).err(), Some(RomError::Empty));
+    assert_eq!(Emulator::with_cpu_rate(&[0; 4000], CLOCK_CPU).err(), Some(RomError::TooLarge { size: 4000, capacity: 3584 }));
+
+    let mut emulator = Emulator::new(&[0x12, 0x00]).unwrap();
+
+    assert_eq!(emulator.set_load_address(0x1000), Err(RomError::Address(0x1000)));
+    assert_eq!(emulator.update_key(16, true), Err(InputError::Key(16)));
+    assert_eq!(emulator.update_key2(0, true), Err(InputError::Keypad2));
+    assert_eq!(emulator.error(), None);
+}
+
+#[test]
+fn out_of_bounds () {
+    let cases: [(&[u8], &str); 8] = [
+        (&[0xAF, 0xFF, 0xD0, 0x02], "Memory access out of bounds from I=FFF at 202"),
+        (&[0xAF, 0xFF, 0xF0, 0x33], "Memory access out of bounds from I=FFF at 202"),
+        (&[0xAF, 0xFE, 0xF2, 0x55], "Memory access out of bounds from I=FFE at 202"),
+        (&[0xAF, 0xFE, 0xF2, 0x65], "Memory access out of bounds from I=FFE at 202"),
+        (&[0x60, 0xFF, 0xE0, 0x9E], "Key FF out of bounds at 202"),
+        (&[0x60, 0x10, 0xE0, 0xA1], "Key 10 out of bounds at 202"),
+        (&[0x1F, 0xFF], "Program counter out of bounds at FFF"),
+        (&[0xAF, 0xFF, 0x60, 0xFF, 0xF0, 0x1E, 0xD0, 0x01], "Memory access out of bounds from I=10FE at 206"),
+    ];
+
+    for (rom, error) in cases.iter() {
+        let mut emulator = Emulator::new(rom).unwrap();
+
+        emulator.frame();
+        assert_eq!(emulator.state(), RunState::Error);
+        assert_eq!(emulator.error().as_deref(), Some(*error));
     }
+
+    let mut emulator = Emulator::new(&[0x60, 0xFF, 0xE0, 0xF2]).unwrap();
+
+    emulator.load_chip8x().unwrap();
+    emulator.frame();
+    assert_eq!(emulator.error(), Some(String::from("Key FF out of bounds at 302")));
+}
+
+#[test]
+fn instructions_per_frame () {
+    // Increment V0, draw, loop
+    let mut emulator = Emulator::new(&[0x70, 0x01, 0xD0, 0x01, 0x12, 0x00]).unwrap();
+
+    emulator.set_instructions_per_frame(9);
+    for _ in 0..10 {
+        emulator.cycle_until_timer();
+    }
+    assert_eq!((emulator.frame_count(), emulator.cycle_count()), (10, 90));
+    assert_eq!(emulator.cpu.v[0], 30);
+
+    emulator.set_quirks(Quirks { vblank: true, ..Quirks::new() });
+    emulator.frame();
+    assert_eq!(emulator.cpu.v[0], 31);
+}
+
+#[test]
+fn vip_timing () {
+    // Clear the screen, then loop on additions
+    let mut emulator = Emulator::new(&[0x00, 0xE0, 0x70, 0x01, 0x12, 0x02]).unwrap();
+
+    emulator.set_vip_timing();
+    emulator.frame();
+    assert_eq!(emulator.cpu.v[0], 0);
+
+    emulator.frame();
+    assert!(emulator.cpu.v[0] > 0);
+    assert_eq!(emulator.cpu_rate(), CLOCK_CPU);
+
+    emulator.reset();
+    assert!(emulator.vip_timing());
+    assert_eq!(emulator.cpu_rate(), CLOCK_CPU);
+}
+
+#[test]
+fn vip_vblank_surplus () {
+    // Draw 15 rows of a sprite unaligned on a byte, which crosses the end of the frame budget
+    let mut emulator = Emulator::new(&[0xD0, 0x0F, 0x12, 0x02]).unwrap();
+
+    emulator.set_vip_timing();
+    emulator.cpu.v[0] = 1;
+    emulator.cpu.frame_cycles = 2000;
+    emulator.cycle();
+
+    assert_eq!(emulator.frame_count(), 1);
+    assert_eq!(emulator.cpu.frame_cycles, 2000 + 40 + 26 + 15 * 66 - crate::cpu::VIP_FRAME_BUDGET);
+}
+
+#[test]
+fn scheduler_switch () {
+    // Increment V0, loop
+    let mut emulator = Emulator::new(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+
+    emulator.set_vip_timing();
+    emulator.cpu.frame_cycles = 2000;
+    emulator.set_instructions_per_frame(10);
+    assert_eq!(emulator.cpu.frame_cycles, 0);
+
+    emulator.cycle_until_timer();
+    assert_eq!((emulator.cycle_count(), emulator.cpu.v[0]), (10, 5));
+    assert_eq!(emulator.cpu_rate(), 600.0);
+}
+
+#[test]
+fn run_frame () {
+    // Draw the 0 glyph
+    let mut emulator = Emulator::new(&[0xD0, 0x05, 0x12, 0x02]).unwrap();
+    let status = emulator.run_frame();
+
+    assert!(status.presented);
+    assert_eq!((status.frame, status.state), (1, RunState::Running));
+    assert_eq!(emulator.framebuffer_len(), 64 * 32 * 4);
+    assert_eq!(&emulator.output.buffer[..8], &[255, 255, 255, 255, 255, 255, 255, 255]);
+    assert_eq!(emulator.output.buffer, emulator.get_framebuffer());
+}
+
+#[test]
+fn key_release () {
+    // Wait for a key into V5, then loop
+    let mut emulator = Emulator::new(&[0xF5, 0x0A, 0x12, 0x02]).unwrap();
+
+    emulator.set_quirks(Quirks { key_release: true, ..Quirks::new() });
+    emulator.update_key(0xA, true).unwrap();
+    emulator.frame();
+    assert_eq!(emulator.cpu.pc, 0x200);
+
+    emulator.update_key(0xA, false).unwrap();
+    emulator.frame();
+    assert_eq!((emulator.cpu.pc, emulator.cpu.v[5]), (0x202, 0xA));
+
+    emulator.reset();
+    emulator.set_quirks(Quirks::new());
+    emulator.update_key(0xB, true).unwrap();
+    emulator.frame();
+    assert_eq!((emulator.cpu.pc, emulator.cpu.v[5]), (0x202, 0xB));
+}
+
+#[test]
+fn flag_last () {
+    // VF := 2, V0 := 1, VF += V0, loop
+    let mut emulator = Emulator::new(&[0x6F, 0x02, 0x60, 0x01, 0x8F, 0x04, 0x12, 0x06]).unwrap();
+
+    emulator.frame();
+    assert_eq!(emulator.cpu.v[0xF], 3);
+
+    emulator.reset();
+    emulator.set_quirks(Quirks { flag_last: true, ..Quirks::new() });
+    emulator.frame();
+    assert_eq!(emulator.cpu.v[0xF], 0);
+}
+
+#[test]
+fn load_address () {
+    // Jump to self at 600
+    let mut emulator = Emulator::new(&[0x16, 0x00]).unwrap();
+
+    emulator.set_load_address(crate::memory::PROGRAM_START_ETI).unwrap();
+    emulator.frame();
+    assert_eq!(emulator.cpu.pc, 0x600);
+    assert_eq!(emulator.rom_info(), RomInfo { size: 2, address: 0x600, capacity: 0xA00, aligned: true });
 }
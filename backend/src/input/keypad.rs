@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+pub const KEYS: usize = 16;
+
+/**
+ * Number of key events kept
+ */
+pub const KEY_EVENTS: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum InputError {
+    /**
+     * Key is not one of the 16 keys of the keypad
+     */
+    Key(usize),
+
+    /**
+     * Second keypad is used on a machine that has none
+     */
+    Keypad2,
+}
+
+impl fmt::Display for InputError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Key(key)    =>  write!(f, "Key {} is out of range", key),
+            InputError::Keypad2     =>  write!(f, "Second keypad is only available on CHIP-8X"),
+        }
+    }
+}
+
+impl From<InputError> for JsValue {
+    fn from (err: InputError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/**
+ * Press or release edge, stamped with the frame and CPU cycle at which it happened
+ */
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+    pub frame: usize,
+    pub cycle: usize,
+}
+
+pub struct Keypad {
+    pub state: [bool; KEYS],
+
+    /**
+     * Latest edges, oldest first
+     */
+    pub events: VecDeque<KeyEvent>,
+
+    /**
+     * Number of edges since power-on, including the ones dropped from `events`
+     */
+    pub sequence: u64,
+}
+
+impl Keypad {
+    pub fn new () -> Self {
+        Self {
+            state: [false; KEYS],
+            events: VecDeque::with_capacity(KEY_EVENTS),
+            sequence: 0,
+        }
+    }
+
+    /**
+     * Updates a key, recording an edge if its state changed
+     */
+    pub fn set (&mut self, key: usize, pressed: bool, frame: usize, cycle: usize) -> Result<(), InputError> {
+        let state = self.state.get_mut(key).ok_or(InputError::Key(key))?;
+
+        if *state != pressed {
+            *state = pressed;
+
+            if self.events.len() == KEY_EVENTS {
+                self.events.pop_front();
+            }
+            self.events.push_back(KeyEvent { key: key as u8, pressed, frame, cycle });
+            self.sequence += 1;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * First key released after the edge with the given sequence number
+     */
+    pub fn released_since (&self, sequence: u64) -> Option<u8> {
+        let first = self.sequence - self.events.len() as u64;
+
+        self.events.iter()
+            .skip(sequence.saturating_sub(first) as usize)
+            .find(|event| !event.pressed)
+            .map(|event| event.key)
+    }
+}
+
+#[test]
+fn edges () {
+    let mut keypad = Keypad::new();
+
+    keypad.set(0x5, true, 1, 10).unwrap();
+    keypad.set(0x5, true, 1, 11).unwrap();
+    let since = keypad.sequence;
+    assert_eq!(keypad.released_since(since), None);
+
+    keypad.set(0x5, false, 2, 20).unwrap();
+    assert_eq!(keypad.events.len(), 2);
+    assert_eq!(keypad.events[1], KeyEvent { key: 0x5, pressed: false, frame: 2, cycle: 20 });
+    assert_eq!(keypad.released_since(since), Some(0x5));
+    assert_eq!(keypad.released_since(keypad.sequence), None);
+    assert_eq!(keypad.set(16, true, 0, 0), Err(InputError::Key(16)));
+}
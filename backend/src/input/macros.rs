@@ -0,0 +1,216 @@
+use wasm_bindgen::prelude::*;
+use crate::{
+    clock::CLOCK_TIMER,
+    input::{InputError, KEYS},
+    Emulator,
+};
+
+/**
+ * Turbo button: while held, its key is pressed and released repeatedly
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AutoFire {
+    /**
+     * Frames per press and release
+     */
+    pub period: usize,
+    pub held: bool,
+
+    /**
+     * Frames since the button was held, or 0 once released
+     */
+    pub frames: usize,
+}
+
+/**
+ * Keypad states to apply on successive frames, one bit per key
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputMacro {
+    pub states: Vec<u16>,
+    pub position: usize,
+}
+
+pub struct Macros {
+    pub autofire: [Option<AutoFire>; KEYS],
+    pub playing: Option<InputMacro>,
+}
+
+impl Default for Macros {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Macros {
+    pub fn new () -> Self {
+        Self {
+            autofire: [None; KEYS],
+            playing: None,
+        }
+    }
+
+    /**
+     * Keys that should be pressed or released on the next frame, as a mask of keys set and a mask of their states
+     */
+    fn next (&mut self) -> (u16, u16) {
+        let (mut mask, mut state) = (0, 0);
+
+        for (key, autofire) in self.autofire.iter_mut().enumerate() {
+            match autofire {
+                Some(autofire) if autofire.held => {
+                    let pressed = autofire.frames % autofire.period < autofire.period / 2;
+
+                    autofire.frames += 1;
+                    mask |= 1 << key;
+                    state |= (pressed as u16) << key;
+                },
+                // Released once after the button, then the key is left alone
+                Some(autofire) if autofire.frames > 0 => {
+                    autofire.frames = 0;
+                    mask |= 1 << key;
+                },
+                _ => {},
+            }
+        }
+
+        if let Some(playing) = &mut self.playing {
+            let previous = playing.position.checked_sub(1).map_or(0, |position| playing.states[position]);
+
+            // Once over, keys pressed by the macro are released
+            let current = playing.states.get(playing.position).copied().unwrap_or(0);
+
+            mask |= previous | current;
+            state = (state & !(previous | current)) | current;
+            playing.position += 1;
+
+            if playing.position > playing.states.len() {
+                self.playing = None;
+            }
+        }
+
+        (mask, state)
+    }
+}
+
+impl Emulator {
+    /**
+     * Applies turbo buttons and macros on a frame boundary, through `update_key` so movies record them
+     */
+    pub (crate) fn macros_frame (&mut self) {
+        let (mask, state) = self.macros.next();
+
+        for key in (0..KEYS).filter(|key| mask >> key & 1 == 1) {
+            let pressed = state >> key & 1 == 1;
+
+            if self.keypad.state[key] != pressed {
+                // Keys are in range
+                let _ = self.update_key(key, pressed);
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Turns a key into a turbo button, pressed this many times per second while held
+     */
+    pub fn autofire_set (&mut self, key: usize, frequency: f64) -> Result<(), JsValue> {
+        if key >= KEYS {
+            return Err(JsValue::from_str(&InputError::Key(key).to_string()));
+        }
+
+        if !(frequency.is_finite() && frequency > 0.0) {
+            return Err(JsValue::from_str(&format!("Invalid turbo frequency {}", frequency)));
+        }
+
+        // A press and a release take at least one frame each
+        let period = ((CLOCK_TIMER / frequency).round() as usize).max(2);
+
+        self.macros.autofire[key] = Some(AutoFire { period, held: false, frames: 0 });
+        Ok(())
+    }
+
+    pub fn autofire_clear (&mut self, key: usize) {
+        if let Some(autofire) = self.macros.autofire.get_mut(key) {
+            *autofire = None;
+        }
+    }
+
+    /**
+     * Holds or releases the turbo button of a key, taking effect on the next frame
+     */
+    pub fn autofire_hold (&mut self, key: usize, held: bool) {
+        if let Some(Some(autofire)) = self.macros.autofire.get_mut(key) {
+            if held && !autofire.held {
+                autofire.frames = 0;
+            }
+
+            autofire.held = held;
+        }
+    }
+
+    /**
+     * Plays keypad states from the next frame on, one per frame with bit N for key N, then releases the keys
+     */
+    pub fn macro_play (&mut self, states: Vec<u16>) {
+        self.macros.playing = Some(InputMacro { states, position: 0 });
+    }
+
+    pub fn macro_playing (&self) -> bool {
+        self.macros.playing.is_some()
+    }
+
+    pub fn macro_stop (&mut self) {
+        self.macros.playing = None;
+    }
+}
+
+#[test]
+fn autofire () {
+    let mut emulator = Emulator::new(&[0x12, 0x00]).unwrap();
+
+    emulator.autofire_set(0x5, 15.0).unwrap();
+    emulator.autofire_hold(0x5, true);
+
+    let states = (0..8).map(|_| {
+        emulator.cycle_until_timer();
+        emulator.keypad.state[0x5]
+    }).collect::<Vec<bool>>();
+    assert_eq!(states, vec![true, true, false, false, true, true, false, false]);
+
+    emulator.autofire_hold(0x5, true);
+    emulator.cycle_until_timer();
+    emulator.autofire_hold(0x5, false);
+    emulator.cycle_until_timer();
+    assert!(!emulator.keypad.state[0x5]);
+
+    // Regular presses are left alone
+    emulator.update_key(0x5, true).unwrap();
+    emulator.cycle_until_timer();
+    assert!(emulator.keypad.state[0x5]);
+}
+
+#[test]
+fn input_macro () {
+    let mut emulator = Emulator::new(&[0x12, 0x00]).unwrap();
+
+    emulator.record();
+    emulator.macro_play(vec![0b0001, 0b0011, 0b0010]);
+
+    let states = (0..4).map(|_| {
+        emulator.cycle_until_timer();
+        (emulator.keypad.state[0], emulator.keypad.state[1])
+    }).collect::<Vec<_>>();
+    assert_eq!(states, vec![(true, false), (true, true), (false, true), (false, false)]);
+    assert!(!emulator.macro_playing());
+
+    let movie = emulator.stop_recording().unwrap();
+    assert_eq!(movie.inputs.iter().map(|input| (input.frame, input.key, input.state)).collect::<Vec<_>>(), vec![
+        (1, 0, true),
+        (2, 1, true),
+        (3, 0, false),
+        (4, 1, false),
+    ]);
+}
@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::prelude::*;
+use crate::{
+    input::{InputError, KEYS},
+    util::sha1::sha1_hex,
+    Emulator,
+};
+
+/**
+ * Built-in keyboard layouts, matching `KeyboardEvent.key` values
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layout {
+    /**
+     * 1234/QWER/ASDF/ZXCV, laid out like the keypad
+     */
+    Classic,
+
+    /**
+     * 1234/AZER/QSDF/WXCV, with the unshifted number row
+     */
+    Azerty,
+
+    /**
+     * Hexadecimal digits on the numeric keypad, with A-F on the operators
+     */
+    Numpad,
+}
+
+/**
+ * Keypad keys, in the order they are laid out on the COSMAC VIP: 123C/456D/789E/A0BF
+ */
+const KEYPAD: [u8; KEYS] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+/**
+ * Host inputs bound to keypad keys. Several inputs may be bound to the same key.
+ * Inputs are `KeyboardEvent.key` values in lowercase, or `gamepad:N` for standard gamepad button N.
+ */
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct Mapping {
+    pub bindings: HashMap<String, u8>,
+}
+
+impl Mapping {
+    pub fn layout (layout: Layout) -> Self {
+        let inputs: &[&str] = match layout {
+            Layout::Classic => &[
+                "1", "2", "3", "4",
+                "q", "w", "e", "r",
+                "a", "s", "d", "f",
+                "z", "x", "c", "v",
+            ],
+            // Number row keys also work shifted
+            Layout::Azerty => &[
+                "&", "é", "\"", "'",
+                "a", "z", "e", "r",
+                "q", "s", "d", "f",
+                "w", "x", "c", "v",
+                "1", "2", "3", "4",
+            ],
+            Layout::Numpad => &[
+                "0", "1", "2", "3", "4", "5", "6", "7",
+                "8", "9", "/", "*", "-", "+", "enter", ".",
+            ],
+        };
+        let keys: Vec<u8> = match layout {
+            Layout::Numpad  =>  (0..KEYS as u8).collect(),
+            _               =>  KEYPAD.iter().cycle().copied().take(inputs.len()).collect(),
+        };
+
+        Self {
+            bindings: inputs.iter().zip(keys).map(|(input, key)| (input.to_string(), key)).collect(),
+        }
+    }
+
+    pub fn get (&self, input: &str) -> Option<u8> {
+        self.bindings.get(&input.to_lowercase()).copied()
+    }
+}
+
+/**
+ * Mapping configuration shared by frontends
+ */
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct InputConfig {
+    pub default: Mapping,
+
+    /**
+     * Mappings for specific ROMs, keyed by their SHA-1
+     */
+    pub roms: HashMap<String, Mapping>,
+}
+
+pub struct InputMapper {
+    pub config: InputConfig,
+
+    /**
+     * SHA-1 of the ROM being run
+     */
+    pub rom: String,
+
+    /**
+     * Host inputs currently held
+     */
+    pub held: HashSet<String>,
+}
+
+impl InputMapper {
+    pub fn new (rom: &[u8]) -> Self {
+        Self {
+            config: InputConfig {
+                default: Mapping::layout(Layout::Classic),
+                roms: HashMap::new(),
+            },
+            rom: sha1_hex(rom),
+            held: HashSet::new(),
+        }
+    }
+
+    /**
+     * Mapping in use for the current ROM
+     */
+    pub fn mapping (&self) -> &Mapping {
+        self.config.roms.get(&self.rom).unwrap_or(&self.config.default)
+    }
+
+    pub fn mapping_mut (&mut self) -> &mut Mapping {
+        match self.config.roms.get_mut(&self.rom) {
+            Some(mapping)   =>  mapping,
+            None            =>  &mut self.config.default,
+        }
+    }
+
+    /**
+     * Updates a host input, and returns the keypad key it is bound to with whether any of its inputs is held
+     */
+    pub fn update (&mut self, input: &str, pressed: bool) -> Option<(u8, bool)> {
+        let input = input.to_lowercase();
+        let key = self.mapping().get(&input)?;
+
+        if pressed {
+            self.held.insert(input);
+        } else {
+            self.held.remove(&input);
+        }
+
+        Some((key, self.held.iter().any(|held| self.mapping().get(held) == Some(key))))
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Feeds a host input through the mapping, and returns whether it is bound, so the host can prevent its default action
+     */
+    pub fn input_update (&mut self, input: &str, pressed: bool) -> Result<bool, InputError> {
+        match self.input.update(input, pressed) {
+            Some((key, pressed))    =>  self.update_key(key as usize, pressed).map(|_| true),
+            None                    =>  Ok(false),
+        }
+    }
+
+    /**
+     * Replaces the mapping in use with a built-in layout
+     */
+    pub fn input_layout (&mut self, layout: Layout) {
+        *self.input.mapping_mut() = Mapping::layout(layout);
+        self.input.held.clear();
+    }
+
+    /**
+     * Binds a host input to a keypad key, in addition to the inputs already bound to it
+     */
+    pub fn input_bind (&mut self, input: &str, key: usize) -> Result<(), JsValue> {
+        if key >= KEYS {
+            return Err(JsValue::from_str(&InputError::Key(key).to_string()));
+        }
+
+        self.input.mapping_mut().bindings.insert(input.to_lowercase(), key as u8);
+        Ok(())
+    }
+
+    pub fn input_unbind (&mut self, input: &str) {
+        self.input.mapping_mut().bindings.remove(&input.to_lowercase());
+    }
+
+    /**
+     * Gives the current ROM a mapping of its own, starting from the default one, or goes back to the default one
+     */
+    pub fn input_rom_mapping (&mut self, enabled: bool) {
+        if enabled {
+            let mapping = self.input.mapping().clone();
+            self.input.config.roms.insert(self.input.rom.clone(), mapping);
+        } else {
+            self.input.config.roms.remove(&self.input.rom);
+        }
+    }
+
+    /**
+     * Default and per-ROM mappings as JSON
+     */
+    pub fn input_config_export (&self) -> String {
+        serde_json::to_string(&self.input.config).unwrap()
+    }
+
+    pub fn input_config_import (&mut self, json: &str) -> Result<(), JsValue> {
+        let config: InputConfig = serde_json::from_str(json).map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        if let Some(&key) = config.default.bindings.values().chain(config.roms.values().flat_map(|mapping| mapping.bindings.values())).find(|&&key| key as usize >= KEYS) {
+            return Err(JsValue::from_str(&InputError::Key(key as usize).to_string()));
+        }
+
+        self.input.config = config;
+        self.input.held.clear();
+        Ok(())
+    }
+}
+
+#[test]
+fn layouts () {
+    let classic = Mapping::layout(Layout::Classic);
+    let azerty = Mapping::layout(Layout::Azerty);
+    let numpad = Mapping::layout(Layout::Numpad);
+
+    assert_eq!((classic.get("Q"), classic.get("4"), classic.get("x")), (Some(0x4), Some(0xC), Some(0x0)));
+    assert_eq!((azerty.get("a"), azerty.get("&"), azerty.get("1"), azerty.get("w")), (Some(0x4), Some(0x1), Some(0x1), Some(0xA)));
+    assert_eq!((numpad.get("7"), numpad.get("Enter"), numpad.get(".")), (Some(0x7), Some(0xE), Some(0xF)));
+}
+
+#[test]
+fn mapper () {
+    let mut emulator = Emulator::new(&[0x12, 0x00]).unwrap();
+
+    emulator.input_bind("gamepad:0", 0x5).unwrap();
+    assert_eq!(emulator.input_update("w", true), Ok(true));
+    assert_eq!(emulator.input_update("gamepad:0", true), Ok(true));
+    assert_eq!(emulator.input_update("Escape", true), Ok(false));
+
+    // Key stays held until all of its inputs are released
+    emulator.input_update("w", false).unwrap();
+    assert!(emulator.keypad.state[0x5]);
+    emulator.input_update("gamepad:0", false).unwrap();
+    assert!(!emulator.keypad.state[0x5]);
+
+    emulator.input_rom_mapping(true);
+    emulator.input_layout(Layout::Numpad);
+    let json = emulator.input_config_export();
+
+    let mut other = Emulator::new(&[0x12, 0x00]).unwrap();
+    other.input_config_import(&json).unwrap();
+    assert_eq!(other.input.mapping().get("5"), Some(0x5));
+    assert_eq!(other.input.config.default.get("gamepad:0"), Some(0x5));
+}
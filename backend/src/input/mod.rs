@@ -0,0 +1,7 @@
+mod keypad;
+mod mapper;
+mod macros;
+
+pub use keypad::*;
+pub use mapper::*;
+pub use macros::*;
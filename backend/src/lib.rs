@@ -1,10 +1,27 @@
 pub mod debug;
 pub mod clock;
+pub mod cartridge;
+pub mod chip8x;
 pub mod cpu;
+pub mod database;
 pub mod memory;
 pub mod display;
 pub mod input;
+pub mod output;
+pub mod palette;
+pub mod phosphor;
+pub mod scale;
+pub mod screenshot;
+pub mod recording;
+pub mod audio;
 pub mod util;
+pub mod cheat;
+pub mod movie;
+pub mod octo;
+pub mod quirks;
+pub mod rng;
+pub mod turbo;
+pub mod vip;
 
 mod emulator;
 pub use emulator::*;
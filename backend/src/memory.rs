@@ -1,28 +1,129 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
 use crate::{display, cpu::Instruction};
 
 pub const MEMORY_SIZE: usize = 4096;
 pub const RESERVED_START: usize = 0;
 pub const PROGRAM_START: usize = 0x200;
 pub const PROGRAM_START_ETI: usize = 0x600;
+pub const PROGRAM_START_CHIP8X: usize = 0x300;
+
+#[derive(Debug, PartialEq)]
+pub enum RomError {
+    Empty,
+
+    /**
+     * ROM does not fit between its load address and the end of memory
+     */
+    TooLarge {
+        size: usize,
+        capacity: usize,
+    },
+
+    /**
+     * Load address overlaps the font or is out of memory
+     */
+    Address(usize),
+}
+
+impl fmt::Display for RomError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Empty                         =>  write!(f, "ROM is empty"),
+            RomError::TooLarge { size, capacity }   =>  write!(f, "ROM of {} bytes does not fit in the {} bytes available", size, capacity),
+            RomError::Address(address)              =>  write!(f, "Cannot load a ROM at {:03X}", address),
+        }
+    }
+}
+
+impl From<RomError> for JsValue {
+    fn from (err: RomError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/**
+ * Where a ROM is loaded, and how it fits in memory
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RomInfo {
+    pub size: usize,
+    pub address: usize,
+
+    /**
+     * Largest ROM that fits from the load address
+     */
+    pub capacity: usize,
+
+    /**
+     * Instructions are 2 bytes long, but an odd size is legitimate when the ROM ends with data
+     */
+    pub aligned: bool,
+}
+
+impl RomInfo {
+    pub fn new (rom: &[u8], address: usize) -> Result<Self, RomError> {
+        if address < RESERVED_START + display::FONT_SET.len() || address >= MEMORY_SIZE {
+            return Err(RomError::Address(address));
+        }
+
+        let capacity = MEMORY_SIZE - address;
+
+        match rom.len() {
+            0                           =>  Err(RomError::Empty),
+            size if size > capacity     =>  Err(RomError::TooLarge { size, capacity }),
+            size                        =>  Ok(Self {
+                size,
+                address,
+                capacity,
+                aligned: size % 2 == 0,
+            }),
+        }
+    }
+}
 
 pub struct Memory {
     pub ram: [u8; MEMORY_SIZE],
 }
 
 impl Memory {
-    pub fn new (rom: &[u8]) -> Memory {
+    /**
+     * Loads a ROM at the given address, usually `PROGRAM_START`
+     */
+    pub fn new (rom: &[u8], address: usize) -> Result<Memory, RomError> {
+        let info = RomInfo::new(rom, address)?;
         let mut ram = [0; MEMORY_SIZE];
 
+        if !info.aligned {
+            log::warn!("ROM has an odd size of {} bytes", info.size);
+        }
+
         // Store font sprites
         ram[RESERVED_START .. RESERVED_START + display::FONT_SET.len()].copy_from_slice(&display::FONT_SET);
-        ram[PROGRAM_START .. PROGRAM_START + rom.len()].copy_from_slice(rom);
+        ram[address .. address + rom.len()].copy_from_slice(rom);
 
-        Memory {
+        Ok(Memory {
             ram,
-        }
+        })
     }
 
-    pub fn fetch (&self, at: u16) -> Instruction {
-        Instruction::new((self.ram[at as usize] as u16) << 8 | (self.ram[at as usize + 1] as u16))
+    /**
+     * Instruction at an address, if it is within memory
+     */
+    pub fn fetch (&self, at: u16) -> Option<Instruction> {
+        self.ram.get(at as usize .. at as usize + 2).map(|bytes| Instruction::new((bytes[0] as u16) << 8 | bytes[1] as u16))
     }
 }
+
+#[test]
+fn load () {
+    assert_eq!(Memory::new(&[], PROGRAM_START).err(), Some(RomError::Empty));
+    assert_eq!(Memory::new(&[0; 3585], PROGRAM_START).err(), Some(RomError::TooLarge { size: 3585, capacity: 3584 }));
+    assert_eq!(Memory::new(&[0; 2], 0x10).err(), Some(RomError::Address(0x10)));
+    assert_eq!(RomInfo::new(&[0; 2561], PROGRAM_START_ETI), Err(RomError::TooLarge { size: 2561, capacity: 2560 }));
+    assert!(!RomInfo::new(&[0; 3], PROGRAM_START).unwrap().aligned);
+
+    let memory = Memory::new(&[0x12, 0x34], PROGRAM_START_ETI).unwrap();
+    assert_eq!(&memory.ram[PROGRAM_START_ETI..PROGRAM_START_ETI + 2], &[0x12, 0x34]);
+}
@@ -0,0 +1,501 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use crate::{
+    clock::Scheduler,
+    input::{InputError, Keypad, KEYS},
+    memory::{RomInfo, PROGRAM_START},
+    quirks::Quirks,
+    util::sha1::sha1_hex,
+    vip::Machine,
+    Emulator,
+};
+
+#[derive(Debug)]
+pub enum MovieError {
+    /**
+     * Movie file could not be decoded
+     */
+    Format(String),
+
+    /**
+     * Movie was recorded with another ROM
+     */
+    Rom(String),
+
+    /**
+     * Movie was recorded on the COSMAC VIP, whose interpreter has to be loaded before playing it
+     */
+    Vip,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Format(reason)  =>  write!(f, "Invalid movie: {}", reason),
+            MovieError::Rom(hash)       =>  write!(f, "Movie was recorded with ROM {}", hash),
+            MovieError::Vip             =>  write!(f, "Movie was recorded on the COSMAC VIP, load its interpreter first"),
+        }
+    }
+}
+
+/**
+ * Key change, stamped with the frame and CPU cycle before which it took effect
+ */
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct MovieInput {
+    pub frame: usize,
+    pub cycle: usize,
+    pub key: usize,
+    pub state: bool,
+
+    /**
+     * Key of the second CHIP-8X keypad
+     */
+    #[serde(default)]
+    pub keypad2: bool,
+}
+
+/**
+ * Speed change, stamped like key changes
+ */
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct MovieSpeed {
+    pub frame: usize,
+    pub cycle: usize,
+    pub rate: f64,
+    pub scheduler: Scheduler,
+}
+
+/**
+ * Quirk change, stamped like key changes
+ */
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct MovieQuirks {
+    pub frame: usize,
+    pub cycle: usize,
+    pub quirks: Quirks,
+}
+
+/**
+ * Everything needed to replay a session from power-on
+ */
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct Movie {
+    /**
+     * SHA-1 of the ROM
+     */
+    pub rom: String,
+    #[serde(default = "default_machine")]
+    pub machine: Machine,
+    pub quirks: Quirks,
+    pub seed: u64,
+
+    /**
+     * CPU frequency in Hz
+     */
+    pub rate: f64,
+    pub scheduler: Scheduler,
+
+    /**
+     * Address the ROM was loaded at
+     */
+    #[serde(default = "default_load_address")]
+    pub load_address: usize,
+
+    /**
+     * Frame at which recording was stopped
+     */
+    pub frames: usize,
+    pub inputs: Vec<MovieInput>,
+    #[serde(default)]
+    pub speeds: Vec<MovieSpeed>,
+    #[serde(default)]
+    pub quirk_changes: Vec<MovieQuirks>,
+}
+
+fn default_load_address () -> usize {
+    PROGRAM_START
+}
+
+fn default_machine () -> Machine {
+    Machine::Chip8
+}
+
+impl Movie {
+    pub fn parse (text: &str) -> Result<Self, MovieError> {
+        let movie: Movie = serde_json::from_str(text).map_err(|err| MovieError::Format(err.to_string()))?;
+
+        if let Some(input) = movie.inputs.iter().find(|input| input.key >= KEYS) {
+            return Err(MovieError::Format(InputError::Key(input.key).to_string()));
+        }
+
+        Ok(movie)
+    }
+
+    pub fn export (&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+pub enum MovieState {
+    Idle,
+    Recording(Movie),
+
+    /**
+     * Recording ended by a reset, kept until it is stopped
+     */
+    Finished(Movie),
+    Playing {
+        movie: Movie,
+        position: usize,
+
+        /**
+         * Next speed change to apply
+         */
+        speed: usize,
+
+        /**
+         * Next quirk change to apply
+         */
+        quirk: usize,
+    },
+}
+
+impl Emulator {
+    /**
+     * Restarts from power-on and records all subsequent input
+     */
+    pub fn record (&mut self) {
+        self.reset();
+        self.movie = MovieState::Recording(Movie {
+            rom: sha1_hex(&self.rom),
+            machine: self.machine(),
+            quirks: self.cpu.quirks,
+            seed: self.seed,
+            rate: self.cpu.clock.rate,
+            scheduler: self.cpu.scheduler,
+            load_address: self.load_address,
+            frames: 0,
+            inputs: Vec::new(),
+            speeds: Vec::new(),
+            quirk_changes: Vec::new(),
+        });
+    }
+
+    pub fn stop_recording (&mut self) -> Option<Movie> {
+        match std::mem::replace(&mut self.movie, MovieState::Idle) {
+            MovieState::Recording(mut movie) => {
+                movie.frames = self.frame_count();
+                Some(movie)
+            },
+            MovieState::Finished(movie) => Some(movie),
+            _ => None,
+        }
+    }
+
+    /**
+     * Restarts from power-on on the movie's machine with its settings, and replays its input, ignoring live input until it ends.
+     * The COSMAC VIP has to be loaded beforehand, as the movie does not hold its interpreter.
+     */
+    pub fn play (&mut self, movie: Movie) -> Result<(), MovieError> {
+        if movie.rom != sha1_hex(&self.rom) {
+            return Err(MovieError::Rom(movie.rom));
+        }
+
+        if movie.machine == Machine::CosmacVip && self.vip.is_none() {
+            return Err(MovieError::Vip);
+        }
+
+        RomInfo::new(&self.rom, movie.load_address).map_err(|err| MovieError::Format(err.to_string()))?;
+
+        match movie.machine {
+            Machine::Chip8      =>  {
+                self.vip = None;
+                self.keypad2 = None;
+            },
+            Machine::Chip8X     =>  {
+                self.vip = None;
+                self.keypad2 = Some(Keypad::new());
+            },
+            Machine::CosmacVip  =>  self.keypad2 = None,
+        }
+
+        self.seed = movie.seed;
+        self.cpu.quirks = movie.quirks;
+        self.cpu.clock.rate = movie.rate;
+        self.cpu.scheduler = movie.scheduler;
+        self.load_address = movie.load_address;
+        self.reset();
+        self.movie = MovieState::Playing {
+            movie,
+            position: 0,
+            speed: 0,
+            quirk: 0,
+        };
+        self.movie_playback();
+
+        Ok(())
+    }
+
+    pub (crate) fn movie_input (&mut self, key: usize, state: bool, keypad2: bool) {
+        let (frame, cycle) = (self.frame_count(), self.cycle_count());
+
+        if let MovieState::Recording(movie) = &mut self.movie {
+            movie.inputs.push(MovieInput { frame, cycle, key, state, keypad2 });
+        }
+    }
+
+    /**
+     * Records the current speed, after it changed
+     */
+    pub (crate) fn movie_speed (&mut self) {
+        let (frame, cycle) = (self.frame_count(), self.cycle_count());
+        let (rate, scheduler) = (self.cpu.clock.rate, self.cpu.scheduler);
+
+        if let MovieState::Recording(movie) = &mut self.movie {
+            movie.speeds.push(MovieSpeed { frame, cycle, rate, scheduler });
+        }
+    }
+
+    /**
+     * Records the current quirks, after they changed
+     */
+    pub (crate) fn movie_quirks (&mut self) {
+        let (frame, cycle, quirks) = (self.frame_count(), self.cycle_count(), self.cpu.quirks);
+
+        if let MovieState::Recording(movie) = &mut self.movie {
+            movie.quirk_changes.push(MovieQuirks { frame, cycle, quirks });
+        }
+    }
+
+    /**
+     * Movies replay from power-on only, so restarting ends recording, keeping the movie until it is stopped, and playback
+     */
+    pub (crate) fn movie_reset (&mut self) {
+        self.movie = match std::mem::replace(&mut self.movie, MovieState::Idle) {
+            MovieState::Recording(mut movie)    =>  {
+                movie.frames = self.frame_count();
+                MovieState::Finished(movie)
+            },
+            MovieState::Finished(movie)         =>  MovieState::Finished(movie),
+            _                                   =>  MovieState::Idle,
+        };
+    }
+
+    /**
+     * Applies inputs, speed and quirk changes that are due before the next cycle, and ends playback once the movie is over
+     */
+    pub (crate) fn movie_playback (&mut self) {
+        let (frame, cycle) = (self.frame_count(), self.cycle_count());
+        let mut speeds = Vec::new();
+
+        if let MovieState::Playing { movie, position, speed, quirk } = &mut self.movie {
+            while let Some(input) = movie.inputs.get(*position).filter(|input| (input.frame, input.cycle) <= (frame, cycle)) {
+                // Keys were checked when parsing, and the second keypad is there as the machine was restored
+                let keypad = if input.keypad2 { self.keypad2.as_mut() } else { Some(&mut self.keypad) };
+
+                if let Some(keypad) = keypad {
+                    let _ = keypad.set(input.key, input.state, frame, cycle);
+                }
+                *position += 1;
+            }
+
+            while let Some(change) = movie.speeds.get(*speed).filter(|change| (change.frame, change.cycle) <= (frame, cycle)) {
+                speeds.push(*change);
+                *speed += 1;
+            }
+
+            while let Some(change) = movie.quirk_changes.get(*quirk).filter(|change| (change.frame, change.cycle) <= (frame, cycle)) {
+                self.cpu.quirks = change.quirks;
+                *quirk += 1;
+            }
+
+            if *position == movie.inputs.len() && *speed == movie.speeds.len() && *quirk == movie.quirk_changes.len() && frame >= movie.frames {
+                self.movie = MovieState::Idle;
+            }
+        }
+
+        for change in speeds {
+            self.set_scheduler(change.rate, change.scheduler);
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    pub fn movie_record (&mut self) {
+        self.record();
+    }
+
+    /**
+     * Stops recording or playback, returning the recorded movie if any
+     */
+    pub fn movie_stop (&mut self) -> Option<String> {
+        let movie = self.stop_recording();
+
+        self.movie = MovieState::Idle;
+        movie.map(|movie| movie.export())
+    }
+
+    pub fn movie_play (&mut self, data: &str) -> Result<(), JsValue> {
+        Movie::parse(data)
+            .and_then(|movie| self.play(movie))
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    pub fn movie_playing (&self) -> bool {
+        matches!(self.movie, MovieState::Playing { .. })
+    }
+}
+
+#[test]
+fn playback () {
+    let rom = [
+        0xC1, 0xFF, // RND V1, FF
+        0xE0, 0x9E, // SKP V0
+        0x12, 0x00, // JMP 200
+        0xA3, 0x00, // LD I, 300
+        0xF1, 0x55, // LD [I], V1
+        0x12, 0x00, // JMP 200
+    ];
+    let mut emulator = Emulator::with_cpu_rate(&rom, 700.0).unwrap();
+
+    emulator.record();
+    for frame in 0..30 {
+        match frame {
+            10 => emulator.update_key(0, true).unwrap(),
+            20 => emulator.update_key(0, false).unwrap(),
+            _ => {},
+        }
+        emulator.cycle_until_timer();
+    }
+
+    let movie = Movie::parse(&emulator.stop_recording().unwrap().export()).unwrap();
+    let mut replay = Emulator::new(&rom).unwrap();
+
+    replay.play(movie).unwrap();
+    for _ in 0..30 {
+        replay.update_key(0, true).unwrap();
+        replay.cycle_until_timer();
+    }
+
+    assert!(!replay.movie_playing());
+    assert_eq!(replay.cpu_rate(), 700.0);
+    assert_ne!(emulator.memory.ram[0x301], 0);
+    assert_eq!(emulator.memory.ram.to_vec(), replay.memory.ram.to_vec());
+    assert_eq!(emulator.cpu.v, replay.cpu.v);
+}
+
+#[test]
+fn speed_changes () {
+    let rom = [
+        0x6F, 0x3C, // LD VF, 3C
+        0xFF, 0x15, // LD DT, VF
+        0x71, 0x01, // ADD V1, 01
+        0xFE, 0x07, // LD VE, DT
+        0x3E, 0x00, // SE VE, 00
+        0x12, 0x04, // JMP 204
+        0x12, 0x0C, // JMP 20C
+    ];
+    let mut emulator = Emulator::with_cpu_rate(&rom, 700.0).unwrap();
+
+    emulator.record();
+    for frame in 0..70 {
+        match frame {
+            10 => emulator.set_cpu_rate(1000.0),
+            20 => emulator.set_instructions_per_frame(9),
+            _ => {},
+        }
+        emulator.cycle_until_timer();
+    }
+
+    let movie = Movie::parse(&emulator.stop_recording().unwrap().export()).unwrap();
+    let mut replay = Emulator::with_cpu_rate(&rom, 700.0).unwrap();
+
+    assert_eq!(movie.speeds.len(), 2);
+    replay.play(movie).unwrap();
+    for _ in 0..70 {
+        replay.cycle_until_timer();
+    }
+
+    assert!(!replay.movie_playing());
+    assert_eq!(replay.instructions_per_frame(), Some(9));
+    assert_eq!(emulator.cpu.v, replay.cpu.v);
+}
+
+#[test]
+fn chip8x () {
+    let rom = [
+        0x72, 0x01, // ADD V2, 01
+        0x64, 0x05, // LD V4, 05
+        0xE4, 0xF2, // SKP2 V4
+        0x13, 0x00, // JMP 300
+        0x13, 0x08, // JMP 308
+    ];
+    let mut emulator = Emulator::new(&rom).unwrap();
+
+    emulator.load_chip8x().unwrap();
+    emulator.record();
+    for frame in 0..20 {
+        if frame == 10 {
+            emulator.update_key2(5, true).unwrap();
+        }
+        emulator.cycle_until_timer();
+    }
+
+    let movie = Movie::parse(&emulator.stop_recording().unwrap().export()).unwrap();
+    let mut replay = Emulator::new(&rom).unwrap();
+
+    assert_eq!(movie.machine, Machine::Chip8X);
+    assert!(movie.inputs[0].keypad2);
+    assert!(matches!(replay.play(Movie { machine: Machine::CosmacVip, ..movie.clone() }), Err(MovieError::Vip)));
+
+    replay.play(movie).unwrap();
+    assert_eq!(replay.machine(), Machine::Chip8X);
+    for _ in 0..20 {
+        replay.cycle_until_timer();
+    }
+
+    assert_eq!(replay.cpu.pc, 0x308);
+    assert_eq!(emulator.cpu.v, replay.cpu.v);
+}
+
+#[test]
+fn quirk_changes_and_reset () {
+    let rom = [
+        0x60, 0x81, // LD V0, 81
+        0x61, 0x04, // LD V1, 04
+        0x80, 0x16, // SHR V0, V1
+        0x83, 0x04, // ADD V3, V0
+        0x12, 0x00, // JMP 200
+    ];
+    let mut emulator = Emulator::new(&rom).unwrap();
+
+    emulator.record();
+    for frame in 0..20 {
+        if frame == 10 {
+            emulator.set_quirks(Quirks { shift: false, ..emulator.quirks() });
+        }
+        emulator.cycle_until_timer();
+    }
+
+    let v = emulator.cpu.v;
+
+    // Resetting ends the recording, and later input is not part of it
+    emulator.reset();
+    emulator.update_key(1, true).unwrap();
+    emulator.cycle_until_timer();
+
+    let movie = emulator.stop_recording().unwrap();
+    let mut replay = Emulator::new(&rom).unwrap();
+
+    assert_eq!((movie.frames, movie.quirk_changes.len(), movie.inputs.len()), (20, 1, 0));
+
+    replay.play(movie).unwrap();
+    for _ in 0..20 {
+        replay.cycle_until_timer();
+    }
+
+    assert!(!replay.quirks().shift);
+    assert_eq!(replay.cpu.v, v);
+}
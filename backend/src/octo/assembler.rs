@@ -0,0 +1,1122 @@
+use std::{collections::HashMap, fmt};
+use crate::{
+    memory::PROGRAM_START,
+    octo::{tokenize, Token},
+};
+
+/**
+ * Assembler for Octo, the CHIP-8 assembly language shared in Octo cartridges.
+ * It covers the statements, directives, control flow, macros and compile-time expressions of the language,
+ * including the SUPER-CHIP and XO-CHIP instructions. Debugger directives (`:breakpoint`, `:monitor`) are skipped.
+ * https://github.com/JohnEarnest/Octo/blob/gh-pages/docs/Manual.md
+ */
+const MAX_ADDRESS: usize = 0x10000;
+
+/**
+ * Macros and string modes expanded over a whole program, beyond which they are assumed to recurse forever
+ */
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct OctoError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for OctoError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+/**
+ * Part of the program to fill once a name used before its definition is known
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Field {
+    /**
+     * 12-bit address of an instruction
+     */
+    Nnn,
+
+    /**
+     * 16-bit address, as used by `i := long` and `:pointer`
+     */
+    Word,
+
+    /**
+     * Address loaded into V0 and V1 by `:unpack`, under a high nibble or in full (`long`)
+     */
+    Unpack(Option<u8>),
+}
+
+struct Reference {
+    address: usize,
+    field: Field,
+    name: String,
+    line: usize,
+}
+
+/**
+ * Open `if ... begin`, `else` or `loop`, with the jumps to fill when it is closed
+ */
+enum Flow {
+    If(usize),
+    Else(usize),
+    Loop { start: usize, whiles: Vec<usize> },
+}
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+    calls: usize,
+}
+
+struct StringMode {
+    alphabet: Vec<char>,
+    body: Vec<Token>,
+}
+
+struct Assembler {
+    /**
+     * Tokens left to assemble, last first, so that macros expand by pushing their body
+     */
+    tokens: Vec<Token>,
+    line: usize,
+    data: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    string_modes: HashMap<String, Vec<StringMode>>,
+    references: Vec<Reference>,
+    flow: Vec<Flow>,
+    expansions: usize,
+}
+
+/**
+ * Assembles Octo source into a ROM loaded at 0x200. Execution starts at the `main` label,
+ * with a jump to it at 0x200 unless the program opens with `: main`.
+ */
+pub fn assemble (source: &str) -> Result<Vec<u8>, OctoError> {
+    let mut tokens = tokenize(source)?;
+
+    tokens.reverse();
+
+    let mut assembler = Assembler {
+        tokens,
+        line: 1,
+        data: Vec::new(),
+        here: PROGRAM_START,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        string_modes: HashMap::new(),
+        references: Vec::new(),
+        flow: Vec::new(),
+        expansions: 0,
+    };
+
+    if !(assembler.peek_is(0, ":") && assembler.peek_is(1, "main")) {
+        assembler.reference(String::from("main"), Field::Nnn);
+        assembler.instruction(0x1000)?;
+    }
+
+    while !assembler.tokens.is_empty() {
+        assembler.statement()?;
+    }
+
+    assembler.finish()
+}
+
+/**
+ * Integer literal in decimal, hexadecimal (`0x`) or binary (`0b`), possibly negative
+ */
+fn number (text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits)    =>  (true, digits),
+        None            =>  (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        digits.parse::<i64>().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative { -value } else { value } as f64)
+}
+
+fn binary (operator: &str, a: f64, b: f64) -> Option<f64> {
+    let (x, y) = (a as i64, b as i64);
+
+    Some(match operator {
+        "+"     =>  a + b,
+        "-"     =>  a - b,
+        "*"     =>  a * b,
+        "/"     =>  a / b,
+        "%"     =>  a % b,
+        "&"     =>  (x & y) as f64,
+        "|"     =>  (x | y) as f64,
+        "^"     =>  (x ^ y) as f64,
+        "<<"    =>  x.checked_shl(y as u32).unwrap_or(0) as f64,
+        ">>"    =>  x.checked_shr(y as u32).unwrap_or(0) as f64,
+        "pow"   =>  a.powf(b),
+        "min"   =>  a.min(b),
+        "max"   =>  a.max(b),
+        "<"     =>  (a < b) as u8 as f64,
+        ">"     =>  (a > b) as u8 as f64,
+        "<="    =>  (a <= b) as u8 as f64,
+        ">="    =>  (a >= b) as u8 as f64,
+        "=="    =>  (a == b) as u8 as f64,
+        "!="    =>  (a != b) as u8 as f64,
+        _       =>  return None,
+    })
+}
+
+fn unary (operator: &str, a: f64) -> Option<f64> {
+    Some(match operator {
+        "-"     =>  -a,
+        "~"     =>  !(a as i64) as f64,
+        "!"     =>  (a == 0.0) as u8 as f64,
+        "sin"   =>  a.sin(),
+        "cos"   =>  a.cos(),
+        "tan"   =>  a.tan(),
+        "exp"   =>  a.exp(),
+        "log"   =>  a.ln(),
+        "abs"   =>  a.abs(),
+        "sqrt"  =>  a.sqrt(),
+        "sign"  =>  if a == 0.0 { 0.0 } else { a.signum() },
+        "ceil"  =>  a.ceil(),
+        "floor" =>  a.floor(),
+        _       =>  return None,
+    })
+}
+
+impl Assembler {
+    fn error<T> (&self, message: String) -> Result<T, OctoError> {
+        Err(OctoError { line: self.line, message })
+    }
+
+    fn peek_is (&self, depth: usize, text: &str) -> bool {
+        self.tokens.len() > depth && self.tokens[self.tokens.len() - 1 - depth].is(text)
+    }
+
+    fn next (&mut self) -> Result<Token, OctoError> {
+        match self.tokens.pop() {
+            Some(token) =>  {
+                self.line = token.line;
+                Ok(token)
+            },
+            None        =>  self.error(String::from("Unexpected end of file")),
+        }
+    }
+
+    fn expect (&mut self, text: &str) -> Result<(), OctoError> {
+        let token = self.next()?;
+
+        if token.is(text) { Ok(()) } else { self.error(format!("Expected '{}', got '{}'", text, token.text)) }
+    }
+
+    fn name (&mut self) -> Result<String, OctoError> {
+        let token = self.next()?;
+
+        if token.string || number(&token.text).is_some() || self.register_of(&token).is_some() {
+            self.error(format!("'{}' is not a valid name", token.text))
+        } else {
+            Ok(token.text)
+        }
+    }
+
+    /**
+     * Tokens up to the matching `}`, the `{` being already read
+     */
+    fn block (&mut self) -> Result<Vec<Token>, OctoError> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+
+        loop {
+            let token = self.next()?;
+
+            if token.is("}") && depth == 0 {
+                return Ok(tokens);
+            }
+
+            depth += token.is("{") as usize;
+            depth -= token.is("}") as usize;
+            tokens.push(token);
+        }
+    }
+
+    fn register_of (&self, token: &Token) -> Option<u8> {
+        let text = token.text.as_str();
+
+        match (token.string, self.aliases.get(text)) {
+            (true, _)                   =>  None,
+            (false, Some(&register))    =>  Some(register),
+            (false, None) if text.len() == 2 && text.starts_with(['v', 'V']) => u8::from_str_radix(&text[1..], 16).ok(),
+            _                           =>  None,
+        }
+    }
+
+    fn is_register (&self) -> bool {
+        self.tokens.last().is_some_and(|token| self.register_of(token).is_some())
+    }
+
+    fn register (&mut self) -> Result<u8, OctoError> {
+        let token = self.next()?;
+
+        match self.register_of(&token) {
+            Some(register)  =>  Ok(register),
+            None            =>  self.error(format!("Expected a register, got '{}'", token.text)),
+        }
+    }
+
+    /**
+     * Value of a number, constant or defined label
+     */
+    fn constant (&self, text: &str) -> Option<f64> {
+        number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|&address| address as f64))
+    }
+
+    /**
+     * Compile-time expression, evaluated from right to left without precedence as in Octo
+     */
+    fn calc (&self, tokens: &[Token]) -> Result<f64, OctoError> {
+        match self.expression(tokens)? {
+            (value, []) if value.is_finite()    =>  Ok(value),
+            (value, [])                         =>  self.error(format!("Expression evaluates to {}", value)),
+            (_, rest)                           =>  self.error(format!("Unexpected '{}' in expression", rest[0].text)),
+        }
+    }
+
+    fn expression<'a> (&self, tokens: &'a [Token]) -> Result<(f64, &'a [Token]), OctoError> {
+        let (left, rest) = self.term(tokens)?;
+
+        match rest.split_first() {
+            Some((operator, rest)) if !operator.string && binary(&operator.text, 0.0, 0.0).is_some() => {
+                let (right, rest) = self.expression(rest)?;
+
+                if (operator.is("/") || operator.is("%")) && right == 0.0 {
+                    return self.error(String::from("Division by zero in expression"));
+                }
+
+                Ok((binary(&operator.text, left, right).unwrap(), rest))
+            },
+            _ => Ok((left, rest)),
+        }
+    }
+
+    fn term<'a> (&self, tokens: &'a [Token]) -> Result<(f64, &'a [Token]), OctoError> {
+        let (token, rest) = match tokens.split_first() {
+            Some(split) =>  split,
+            None        =>  return self.error(String::from("Expression is incomplete")),
+        };
+
+        if token.is("(") {
+            match self.expression(rest)? {
+                (value, [close, rest @ ..]) if close.is(")") => Ok((value, rest)),
+                _ => self.error(String::from("Missing a closing ')' in expression")),
+            }
+        } else if token.is("@") {
+            let (address, rest) = self.expression(rest)?;
+            let byte = (address as usize).checked_sub(PROGRAM_START).and_then(|offset| self.data.get(offset));
+
+            Ok((byte.copied().unwrap_or(0) as f64, rest))
+        } else if let (false, Some(_)) = (token.string, unary(&token.text, 0.0)) {
+            let (value, rest) = self.expression(rest)?;
+
+            Ok((unary(&token.text, value).unwrap(), rest))
+        } else {
+            let value = match token.text.as_str() {
+                _ if token.string   =>  None,
+                "HERE"              =>  Some(self.here as f64),
+                "PI"                =>  Some(std::f64::consts::PI),
+                "E"                 =>  Some(std::f64::consts::E),
+                text                =>  self.constant(text).or_else(|| text.parse::<f64>().ok()),
+            };
+
+            match value {
+                Some(value) =>  Ok((value, rest)),
+                None        =>  self.error(format!("Undefined name '{}' in expression", token.text)),
+            }
+        }
+    }
+
+    /**
+     * Value of the next token or `{ expression }`
+     */
+    fn value (&mut self) -> Result<Option<f64>, OctoError> {
+        let token = self.next()?;
+
+        if token.is("{") {
+            let tokens = self.block()?;
+
+            self.calc(&tokens).map(Some)
+        } else if token.string {
+            self.error(format!("Expected a value, got \"{}\"", token.text))
+        } else if let Some(value) = self.constant(&token.text) {
+            Ok(Some(value))
+        } else {
+            self.tokens.push(token);
+            Ok(None)
+        }
+    }
+
+    fn defined_value (&mut self) -> Result<f64, OctoError> {
+        match self.value()? {
+            Some(value) =>  Ok(value),
+            None        =>  {
+                let token = self.next()?;
+
+                self.error(format!("Undefined name '{}'", token.text))
+            },
+        }
+    }
+
+    /**
+     * Value that fits in a byte, from -128 to 255
+     */
+    fn byte (&mut self) -> Result<u8, OctoError> {
+        let value = self.defined_value()?;
+
+        if value.is_finite() && (-128.0..256.0).contains(&value) {
+            Ok(value as i64 as u8)
+        } else {
+            self.error(format!("Value {} does not fit in a byte", value))
+        }
+    }
+
+    fn nibble (&mut self) -> Result<u8, OctoError> {
+        let value = self.defined_value()?;
+
+        if (0.0..16.0).contains(&value) {
+            Ok(value as u8)
+        } else {
+            self.error(format!("Value {} does not fit in a nibble", value))
+        }
+    }
+
+    /**
+     * Address for the field of what is emitted next, filled later if its name is not defined yet
+     */
+    fn address (&mut self, field: Field) -> Result<u16, OctoError> {
+        let value = match self.value()? {
+            Some(value) =>  value as i64,
+            None        =>  {
+                let name = self.name()?;
+
+                self.reference(name, field);
+                return Ok(0);
+            },
+        };
+
+        self.check_address(value, field)
+    }
+
+    fn check_address (&self, value: i64, field: Field) -> Result<u16, OctoError> {
+        let max = match field {
+            Field::Nnn | Field::Unpack(Some(_)) =>  0xFFF,
+            Field::Word | Field::Unpack(None)   =>  0xFFFF,
+        };
+
+        if (0..=max).contains(&value) {
+            Ok(value as u16)
+        } else {
+            self.error(format!("Address 0x{:X} is out of range", value))
+        }
+    }
+
+    /**
+     * Current address, as the target of a jump
+     */
+    fn here_address (&self) -> Result<u16, OctoError> {
+        self.check_address(self.here as i64, Field::Nnn)
+    }
+
+    fn reference (&mut self, name: String, field: Field) {
+        self.references.push(Reference { address: self.here, field, name, line: self.line });
+    }
+
+    fn emit (&mut self, byte: u8) -> Result<(), OctoError> {
+        if !(PROGRAM_START..MAX_ADDRESS).contains(&self.here) {
+            return self.error(format!("Data at 0x{:X} is outside of program memory", self.here));
+        }
+
+        let offset = self.here - PROGRAM_START;
+
+        if offset >= self.data.len() {
+            self.data.resize(offset + 1, 0);
+        }
+
+        self.data[offset] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn instruction (&mut self, opcode: u16) -> Result<(), OctoError> {
+        self.emit((opcode >> 8) as u8)?;
+        self.emit(opcode as u8)
+    }
+
+    fn fill (&mut self, address: usize, field: Field, value: u16) {
+        let offset = address - PROGRAM_START;
+
+        match field {
+            Field::Nnn                  =>  {
+                self.data[offset] = self.data[offset] & 0xF0 | (value >> 8) as u8;
+                self.data[offset + 1] = value as u8;
+            },
+            Field::Word                 =>  {
+                self.data[offset] = (value >> 8) as u8;
+                self.data[offset + 1] = value as u8;
+            },
+            Field::Unpack(Some(nibble)) =>  {
+                self.data[offset + 1] = nibble << 4 | (value >> 8) as u8 & 0xF;
+                self.data[offset + 3] = value as u8;
+            },
+            Field::Unpack(None)         =>  {
+                self.data[offset + 1] = (value >> 8) as u8;
+                self.data[offset + 3] = value as u8;
+            },
+        }
+    }
+
+    fn define_label (&mut self, name: String, address: usize) -> Result<(), OctoError> {
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) || self.macros.contains_key(&name) {
+            return self.error(format!("The name '{}' has already been defined", name));
+        }
+
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn statement (&mut self) -> Result<(), OctoError> {
+        let token = self.next()?;
+
+        if token.string {
+            return self.error(format!("Unexpected string \"{}\"", token.text));
+        }
+
+        if let Some(x) = self.register_of(&token) {
+            return self.assignment(x as u16);
+        }
+
+        match token.text.as_str() {
+            ":"             =>  {
+                let name = self.name()?;
+
+                self.define_label(name, self.here)?;
+            },
+            ":next"         =>  {
+                let name = self.name()?;
+
+                self.define_label(name, self.here + 1)?;
+            },
+            ":const"        =>  {
+                let name = self.name()?;
+                let value = self.defined_value()?;
+
+                self.constants.insert(name, value);
+            },
+            ":calc"         =>  {
+                let name = self.name()?;
+
+                self.expect("{")?;
+                let tokens = self.block()?;
+                let value = self.calc(&tokens)?;
+
+                self.constants.insert(name, value);
+            },
+            ":alias"        =>  {
+                let name = self.name()?;
+                let register = self.register()?;
+
+                self.aliases.insert(name, register);
+            },
+            ":org"          =>  self.here = self.address(Field::Word)? as usize,
+            ":byte"         =>  match self.tokens.last() {
+                Some(token) if token.is("{") => {
+                    let value = self.defined_value()?;
+
+                    self.emit(value as i64 as u8)?;
+                },
+                _ => {
+                    let byte = self.byte()?;
+
+                    self.emit(byte)?;
+                },
+            },
+            ":pointer"      =>  {
+                let address = self.address(Field::Word)?;
+
+                self.instruction(address)?;
+            },
+            ":call"         =>  {
+                let address = self.address(Field::Nnn)?;
+
+                self.instruction(0x2000 | address)?;
+            },
+            ":unpack"       =>  {
+                let field = if self.peek_is(0, "long") {
+                    self.next()?;
+                    Field::Unpack(None)
+                } else {
+                    Field::Unpack(Some(self.nibble()?))
+                };
+                let address = self.address(field)?;
+
+                self.instruction(0x6000)?;
+                self.instruction(0x6100)?;
+                self.fill(self.here - 4, field, address);
+            },
+            ":macro"        =>  {
+                let name = self.name()?;
+                let mut args = Vec::new();
+
+                while !self.peek_is(0, "{") {
+                    args.push(self.name()?);
+                }
+
+                self.next()?;
+                let body = self.block()?;
+
+                self.macros.insert(name, Macro { args, body, calls: 0 });
+            },
+            ":stringmode"   =>  {
+                let name = self.name()?;
+                let alphabet = self.next()?;
+
+                if !alphabet.string {
+                    return self.error(format!("Expected a string of characters, got '{}'", alphabet.text));
+                }
+
+                self.expect("{")?;
+                let body = self.block()?;
+
+                self.string_modes.entry(name).or_default().push(StringMode { alphabet: alphabet.text.chars().collect(), body });
+            },
+            ":assert"       =>  {
+                let message = match self.tokens.last() {
+                    Some(token) if token.string =>  Some(self.next()?.text),
+                    _                           =>  None,
+                };
+
+                if self.defined_value()? == 0.0 {
+                    return self.error(format!("Assertion failed{}", message.map(|message| format!(": {}", message)).unwrap_or_default()));
+                }
+            },
+            ":breakpoint"   =>  {
+                self.next()?;
+            },
+            ":monitor"      =>  {
+                self.next()?;
+                self.next()?;
+            },
+            "return" | ";"  =>  self.instruction(0x00EE)?,
+            "clear"         =>  self.instruction(0x00E0)?,
+            "hires"         =>  self.instruction(0x00FF)?,
+            "lores"         =>  self.instruction(0x00FE)?,
+            "exit"          =>  self.instruction(0x00FD)?,
+            "scroll-right"  =>  self.instruction(0x00FB)?,
+            "scroll-left"   =>  self.instruction(0x00FC)?,
+            "scroll-down"   =>  {
+                let n = self.nibble()? as u16;
+
+                self.instruction(0x00C0 | n)?;
+            },
+            "scroll-up"     =>  {
+                let n = self.nibble()? as u16;
+
+                self.instruction(0x00D0 | n)?;
+            },
+            "audio"         =>  self.instruction(0xF002)?,
+            "plane"         =>  {
+                let n = self.nibble()? as u16;
+
+                self.instruction(0xF001 | n << 8)?;
+            },
+            "bcd"           =>  {
+                let x = self.register()? as u16;
+
+                self.instruction(0xF033 | x << 8)?;
+            },
+            "save" | "load" =>  {
+                let x = self.register()? as u16;
+                let save = token.text == "save";
+
+                if self.peek_is(0, "-") {
+                    self.next()?;
+                    let y = self.register()? as u16;
+
+                    self.instruction(if save { 0x5002 } else { 0x5003 } | x << 8 | y << 4)?;
+                } else {
+                    self.instruction(if save { 0xF055 } else { 0xF065 } | x << 8)?;
+                }
+            },
+            "saveflags"     =>  {
+                let x = self.register()? as u16;
+
+                self.instruction(0xF075 | x << 8)?;
+            },
+            "loadflags"     =>  {
+                let x = self.register()? as u16;
+
+                self.instruction(0xF085 | x << 8)?;
+            },
+            "sprite"        =>  {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.nibble()? as u16;
+
+                self.instruction(0xD000 | x << 8 | y << 4 | n)?;
+            },
+            "jump"          =>  {
+                let address = self.address(Field::Nnn)?;
+
+                self.instruction(0x1000 | address)?;
+            },
+            "jump0"         =>  {
+                let address = self.address(Field::Nnn)?;
+
+                self.instruction(0xB000 | address)?;
+            },
+            "native"        =>  {
+                let address = self.address(Field::Nnn)?;
+
+                self.instruction(address)?;
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()? as u16;
+                let opcode = match token.text.as_str() {
+                    "delay"     =>  0xF015,
+                    "buzzer"    =>  0xF018,
+                    _           =>  0xF03A,
+                };
+
+                self.instruction(opcode | x << 8)?;
+            },
+            "i"             =>  self.index()?,
+            "if"            =>  {
+                // The condition is negated to skip a jump over the block, rather than the next statement
+                let block = self.tokens.iter().rev().find(|token| token.is("then") || token.is("begin")).map(|token| token.is("begin"));
+
+                match block {
+                    Some(begin) =>  self.condition(begin)?,
+                    None        =>  return self.error(String::from("Expected 'then' or 'begin' after a condition")),
+                }
+
+                let keyword = self.next()?;
+
+                if keyword.is("begin") {
+                    self.flow.push(Flow::If(self.here));
+                    self.instruction(0x1000)?;
+                } else if !keyword.is("then") {
+                    return self.error(format!("Expected 'then' or 'begin', got '{}'", keyword.text));
+                }
+            },
+            "else"          =>  match self.flow.pop() {
+                Some(Flow::If(jump))    =>  {
+                    self.flow.push(Flow::Else(self.here));
+                    self.instruction(0x1000)?;
+                    self.fill(jump, Field::Nnn, self.here_address()?);
+                },
+                _                       =>  return self.error(String::from("'else' without a matching 'if ... begin'")),
+            },
+            "end"           =>  match self.flow.pop() {
+                Some(Flow::If(jump)) | Some(Flow::Else(jump))   =>  self.fill(jump, Field::Nnn, self.here_address()?),
+                _                                               =>  return self.error(String::from("'end' without a matching 'begin'")),
+            },
+            "loop"          =>  self.flow.push(Flow::Loop { start: self.here, whiles: Vec::new() }),
+            "while"         =>  {
+                if !self.flow.iter().any(|flow| matches!(flow, Flow::Loop { .. })) {
+                    return self.error(String::from("'while' outside of a loop"));
+                }
+
+                self.condition(true)?;
+                let here = self.here;
+
+                if let Some(Flow::Loop { whiles, .. }) = self.flow.iter_mut().rev().find(|flow| matches!(flow, Flow::Loop { .. })) {
+                    whiles.push(here);
+                }
+
+                self.instruction(0x1000)?;
+            },
+            "again"         =>  match self.flow.pop() {
+                Some(Flow::Loop { start, whiles }) => {
+                    let start = self.check_address(start as i64, Field::Nnn)?;
+
+                    self.instruction(0x1000 | start)?;
+
+                    let here = self.here_address()?;
+
+                    for jump in whiles {
+                        self.fill(jump, Field::Nnn, here);
+                    }
+                },
+                _ => return self.error(String::from("'again' without a matching 'loop'")),
+            },
+            name if self.macros.contains_key(name) => self.expand_macro(token.text)?,
+            name if self.string_modes.contains_key(name) => self.expand_string(token.text)?,
+            name if name.starts_with(':') || name == "{" || name == "}" || number(name).is_some() => return self.error(format!("Unexpected '{}'", name)),
+            name if self.constant(name).is_some() => {
+                let address = self.check_address(self.constant(name).unwrap() as i64, Field::Nnn)?;
+
+                self.instruction(0x2000 | address)?;
+            },
+
+            // Call to a subroutine, which may be defined later
+            _ => {
+                self.reference(token.text, Field::Nnn);
+                self.instruction(0x2000)?;
+            },
+        }
+
+        Ok(())
+    }
+
+    fn assignment (&mut self, x: u16) -> Result<(), OctoError> {
+        let operator = self.next()?;
+        let opcode = match (operator.text.as_str(), self.is_register()) {
+            (":=", true)    =>  0x8000,
+            ("|=", true)    =>  0x8001,
+            ("&=", true)    =>  0x8002,
+            ("^=", true)    =>  0x8003,
+            ("+=", true)    =>  0x8004,
+            ("-=", true)    =>  0x8005,
+            (">>=", true)   =>  0x8006,
+            ("=-", true)    =>  0x8007,
+            ("<<=", true)   =>  0x800E,
+            (":=", false)   =>  {
+                let opcode = if self.peek_is(0, "random") {
+                    self.next()?;
+                    0xC000 | self.byte()? as u16
+                } else if self.peek_is(0, "key") {
+                    self.next()?;
+                    0xF00A
+                } else if self.peek_is(0, "delay") {
+                    self.next()?;
+                    0xF007
+                } else {
+                    0x6000 | self.byte()? as u16
+                };
+
+                return self.instruction(opcode | x << 8);
+            },
+            ("+=", false)   =>  {
+                let n = self.byte()? as u16;
+
+                return self.instruction(0x7000 | x << 8 | n);
+            },
+            ("-=", false)   =>  {
+                let n = self.byte()?.wrapping_neg() as u16;
+
+                return self.instruction(0x7000 | x << 8 | n);
+            },
+            _               =>  return self.error(format!("Unknown operator '{}' on a register", operator.text)),
+        };
+        let y = self.register()? as u16;
+
+        self.instruction(opcode | x << 8 | y << 4)
+    }
+
+    fn index (&mut self) -> Result<(), OctoError> {
+        let operator = self.next()?;
+
+        if operator.is("+=") {
+            let x = self.register()? as u16;
+
+            return self.instruction(0xF01E | x << 8);
+        } else if !operator.is(":=") {
+            return self.error(format!("Unknown operator '{}' on i", operator.text));
+        }
+
+        if self.peek_is(0, "hex") || self.peek_is(0, "bighex") {
+            let opcode = if self.next()?.text == "hex" { 0xF029 } else { 0xF030 };
+            let x = self.register()? as u16;
+
+            self.instruction(opcode | x << 8)
+        } else if self.peek_is(0, "long") {
+            self.next()?;
+            self.instruction(0xF000)?;
+            let address = self.address(Field::Word)?;
+
+            self.instruction(address)
+        } else {
+            let address = self.address(Field::Nnn)?;
+
+            self.instruction(0xA000 | address)
+        }
+    }
+
+    /**
+     * Skip instruction for a condition: the next instruction runs when the condition holds, or when it does not if negated.
+     * Comparisons subtract in VF, or in the register aliased as `compare-temp`.
+     */
+    fn condition (&mut self, negated: bool) -> Result<(), OctoError> {
+        let x = self.register()? as u16;
+        let operator = self.next()?;
+        let operator = match (negated, operator.text.as_str()) {
+            (false, operator)   =>  operator,
+            (true, "==")        =>  "!=",
+            (true, "!=")        =>  "==",
+            (true, "<")         =>  ">=",
+            (true, ">")         =>  "<=",
+            (true, "<=")        =>  ">",
+            (true, ">=")        =>  "<",
+            (true, "key")       =>  "-key",
+            (true, "-key")      =>  "key",
+            (true, operator)    =>  operator,
+        };
+
+        match operator {
+            "key"   =>  return self.instruction(0xE0A1 | x << 8),
+            "-key"  =>  return self.instruction(0xE09E | x << 8),
+            "==" | "!=" if self.is_register() => {
+                let y = self.register()? as u16;
+
+                return self.instruction(if operator == "==" { 0x9000 } else { 0x5000 } | x << 8 | y << 4);
+            },
+            "==" | "!=" => {
+                let n = self.byte()? as u16;
+
+                return self.instruction(if operator == "==" { 0x4000 } else { 0x3000 } | x << 8 | n);
+            },
+            "<" | ">" | "<=" | ">=" => (),
+            operator => return self.error(format!("Expected a comparison, got '{}'", operator)),
+        }
+
+        let temp = self.aliases.get("compare-temp").copied().unwrap_or(0xF) as u16;
+
+        if self.is_register() {
+            let y = self.register()? as u16;
+
+            self.instruction(0x8000 | temp << 8 | y << 4)?;
+        } else {
+            let n = self.byte()? as u16;
+
+            self.instruction(0x6000 | temp << 8 | n)?;
+        }
+
+        let (subtraction, skip) = match operator {
+            "<"     =>  (0x7, 0x3F01),
+            ">"     =>  (0x5, 0x3F01),
+            "<="    =>  (0x5, 0x4F01),
+            _       =>  (0x7, 0x4F01),
+        };
+
+        self.instruction(0x8000 | temp << 8 | x << 4 | subtraction)?;
+        self.instruction(skip)
+    }
+
+    fn expand (&mut self, name: &str) -> Result<(), OctoError> {
+        self.expansions += 1;
+
+        if self.expansions > MAX_EXPANSIONS {
+            return self.error(format!("'{}' expands more than {} times, it may be recursive", name, MAX_EXPANSIONS));
+        }
+
+        Ok(())
+    }
+
+    fn expand_macro (&mut self, name: String) -> Result<(), OctoError> {
+        self.expand(&name)?;
+
+        let count = self.macros[&name].args.len();
+        let args = (0..count).map(|_| self.next()).collect::<Result<Vec<_>, _>>()?;
+        let definition = self.macros.get_mut(&name).unwrap();
+        let calls = Token { text: definition.calls.to_string(), line: self.line, string: false };
+        let body: Vec<Token> = definition.body
+            .iter()
+            .map(|token| match definition.args.iter().position(|arg| token.is(arg)) {
+                Some(n)                     =>  args[n].clone(),
+                None if token.is("CALLS")   =>  calls.clone(),
+                None                        =>  token.clone(),
+            })
+            .collect();
+
+        definition.calls += 1;
+        self.tokens.extend(body.into_iter().rev());
+        Ok(())
+    }
+
+    /**
+     * Expands the body of the string mode for each character of a string, with `CHAR` its code,
+     * `INDEX` its position in the string and `VALUE` its position in the alphabet
+     */
+    fn expand_string (&mut self, name: String) -> Result<(), OctoError> {
+        self.expand(&name)?;
+
+        let text = self.next()?;
+
+        if !text.string {
+            return self.error(format!("Expected a string after '{}', got '{}'", name, text.text));
+        }
+
+        let mut body = Vec::new();
+
+        for (index, c) in text.text.chars().enumerate() {
+            let (mode, value) = match self.string_modes[&name].iter().find_map(|mode| Some((mode, mode.alphabet.iter().position(|&a| a == c)?))) {
+                Some(found) =>  found,
+                None        =>  return self.error(format!("String mode '{}' is not defined for the character '{}'", name, c)),
+            };
+            let number = |value: usize| Token { text: value.to_string(), line: text.line, string: false };
+
+            body.extend(mode.body.iter().map(|token| match token.text.as_str() {
+                _ if token.string   =>  token.clone(),
+                "CHAR"              =>  number(c as usize),
+                "INDEX"             =>  number(index),
+                "VALUE"             =>  number(value),
+                _                   =>  token.clone(),
+            }));
+        }
+
+        self.tokens.extend(body.into_iter().rev());
+        Ok(())
+    }
+
+    fn finish (mut self) -> Result<Vec<u8>, OctoError> {
+        if !self.flow.is_empty() {
+            return self.error(String::from("A 'begin' or 'loop' is never closed"));
+        }
+
+        for reference in std::mem::take(&mut self.references) {
+            let value = match self.constant(&reference.name) {
+                Some(value) =>  value as i64,
+                None        =>  return Err(OctoError { line: reference.line, message: format!("Undefined name '{}'", reference.name) }),
+            };
+
+            self.line = reference.line;
+            let value = self.check_address(value, reference.field)?;
+
+            self.fill(reference.address, reference.field, value);
+        }
+
+        Ok(self.data)
+    }
+}
+
+#[test]
+fn statements () {
+    let source = "
+        : main
+            clear
+            v0 := 5  v1 += v0  v2 -= 1  va := random 0xFF  v3 := key  i := digit
+            sprite v0 v1 5
+            delay := v0  buzzer := v1  i += v2  i := hex v3  bcd v4
+            save v5  load v6
+            jump main
+        : digit
+            0xF0
+    ";
+
+    assert_eq!(assemble(source).err().map(|err| err.line), Some(10));
+    assert_eq!(assemble(&source.replace("0xF0", ":byte 0xF0")).unwrap(), vec![
+        0x00, 0xE0,
+        0x60, 0x05, 0x81, 0x04, 0x72, 0xFF, 0xCA, 0xFF, 0xF3, 0x0A, 0xA2, 0x20,
+        0xD0, 0x15,
+        0xF0, 0x15, 0xF1, 0x18, 0xF2, 0x1E, 0xF3, 0x29, 0xF4, 0x33,
+        0xF5, 0x55, 0xF6, 0x65,
+        0x12, 0x00,
+        0xF0,
+    ]);
+}
+
+#[test]
+fn control_flow () {
+    let source = "
+        : draw
+            return
+        : main
+            loop
+                if v0 == 3 then v1 := 1
+                if v0 < v2 begin
+                    draw
+                else
+                    v0 += 1
+                end
+                while v0 != 10
+            again
+    ";
+
+    // Jump to main, which follows the subroutine
+    assert_eq!(assemble(source).unwrap(), vec![
+        0x12, 0x04,
+        0x00, 0xEE,
+        0x40, 0x03, 0x61, 0x01,
+        0x8F, 0x20, 0x8F, 0x07, 0x4F, 0x01, 0x12, 0x14,
+        0x22, 0x02, 0x12, 0x16,
+        0x70, 0x01,
+        0x40, 0x0A, 0x12, 0x1C,
+        0x12, 0x04,
+    ]);
+    assert!(assemble(": main loop").is_err());
+    assert!(assemble(": main end").is_err());
+}
+
+#[test]
+fn directives () {
+    let source = "
+        :alias x v4
+        :const SPEED 3
+        :calc DOUBLE { SPEED * 2 + 1 }
+        :macro add register amount { register += amount }
+        :stringmode text \"ab\" { :byte { VALUE + 1 } }
+        : main
+            add x SPEED
+            add v1 DOUBLE
+            :unpack 0xA data
+            i := long data
+            :next target
+            v2 := 0
+        : data
+            :pointer target
+            text \"ba\"
+        :org 0x300
+            :byte -1
+    ";
+    let rom = assemble(source).unwrap();
+
+    // Jump to main, as the program does not open with it
+    assert_eq!(&rom[..22], &[
+        0x12, 0x02,
+        0x74, 0x03,
+        0x71, 0x09,
+        0x60, 0xA2, 0x61, 0x10,
+        0xF0, 0x00, 0x02, 0x10,
+        0x62, 0x00,
+        0x02, 0x0F,
+        0x02, 0x01,
+        0x00, 0x00,
+    ]);
+    assert_eq!(rom.len(), 0x101);
+    assert_eq!(rom[0x100], 0xFF);
+    assert_eq!(assemble(": main v0 := 256").err().map(|err| err.message), Some(String::from("Value 256 does not fit in a byte")));
+    assert_eq!(assemble("v0 := 1").err().map(|err| err.message), Some(String::from("Undefined name 'main'")));
+    assert!(assemble(": main :assert \"fails\" { 1 == 2 }").is_err());
+}
+
+#[test]
+fn errors () {
+    let message = |source: &str| assemble(source).err().map(|err| err.message);
+
+    assert_eq!(message(":macro m { m } : main m"), Some(format!("'m' expands more than {} times, it may be recursive", MAX_EXPANSIONS)));
+    assert_eq!(message(":stringmode s \"a\" { s \"a\" } : main s \"a\""), Some(format!("'s' expands more than {} times, it may be recursive", MAX_EXPANSIONS)));
+    assert_eq!(message(":calc X { 1 / 0 } : main :byte { X }"), Some(String::from("Division by zero in expression")));
+    assert_eq!(message(": main :byte { 1 % 0 }"), Some(String::from("Division by zero in expression")));
+    assert_eq!(message(": main :byte { log 0 }"), Some(String::from("Expression evaluates to -inf")));
+    assert_eq!(message(": main { v0 := 1 }"), Some(String::from("Unexpected '{'")));
+    assert_eq!(message(": main }"), Some(String::from("Unexpected '}'")));
+    assert_eq!(message(": main :org 0xFFE loop again"), Some(String::from("Address 0x1000 is out of range")));
+    assert_eq!(message(": main :org 0xFFC if v0 == 0 begin end"), Some(String::from("Address 0x1000 is out of range")));
+    assert_eq!(message(": main :org 0xFFA if v0 == 0 begin else end"), Some(String::from("Address 0x1000 is out of range")));
+    assert_eq!(message(": main :org 0x1000 loop v0 += 1 again"), Some(String::from("Address 0x1000 is out of range")));
+}
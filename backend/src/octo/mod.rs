@@ -0,0 +1,5 @@
+mod assembler;
+mod tokenizer;
+
+pub use assembler::*;
+pub use tokenizer::*;
@@ -0,0 +1,87 @@
+use crate::octo::OctoError;
+
+/**
+ * Word of Octo source, with the line it is on
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+
+    /**
+     * Quoted string, which is never taken as a name or a number
+     */
+    pub string: bool,
+}
+
+impl Token {
+    pub fn is (&self, text: &str) -> bool {
+        !self.string && self.text == text
+    }
+}
+
+/**
+ * Splits source on whitespace. Comments run from `#` to the end of the line, strings are quoted and support the usual escapes.
+ */
+pub fn tokenize (source: &str) -> Result<Vec<Token>, OctoError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(&c) = chars.peek() {
+        if c == '\n' {
+            line += 1;
+            chars.next();
+        } else if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            while chars.peek().is_some_and(|&c| c != '\n') {
+                chars.next();
+            }
+        } else if c == '"' {
+            let mut text = String::new();
+
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"')   =>  break,
+                    Some('\\')  =>  text.push(match chars.next() {
+                        Some('n')   =>  '\n',
+                        Some('r')   =>  '\r',
+                        Some('t')   =>  '\t',
+                        Some('0')   =>  '\0',
+                        Some(c)     =>  c,
+                        None        =>  return Err(OctoError { line, message: String::from("Missing a closing \" in a string literal") }),
+                    }),
+                    Some(c)     =>  {
+                        line += (c == '\n') as usize;
+                        text.push(c);
+                    },
+                    None        =>  return Err(OctoError { line, message: String::from("Missing a closing \" in a string literal") }),
+                }
+            }
+
+            tokens.push(Token { text, line, string: true });
+        } else {
+            let mut text = String::new();
+
+            while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace()) {
+                text.push(c);
+                chars.next();
+            }
+
+            tokens.push(Token { text, line, string: false });
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[test]
+fn tokens () {
+    let tokens = tokenize(": main # comment\n  v0 := \"a \\\"b\\\"\"\n").unwrap();
+    let texts: Vec<_> = tokens.iter().map(|token| (token.text.as_str(), token.line, token.string)).collect();
+
+    assert_eq!(texts, vec![(":", 1, false), ("main", 1, false), ("v0", 2, false), (":=", 2, false), ("a \"b\"", 2, true)]);
+    assert!(tokenize("\"open").is_err());
+}
@@ -0,0 +1,167 @@
+use wasm_bindgen::prelude::*;
+use crate::{
+    display::{Display, VRAM_SIZE},
+    palette::{Palette, Theme},
+    phosphor::Phosphor,
+};
+
+/**
+ * Layout of the output buffer. Pixels are stored row by row.
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat {
+    /**
+     * 1 bit per pixel, most significant bit first. Phosphor persistence does not apply.
+     */
+    Packed1,
+
+    /**
+     * 1 byte per pixel, holding the palette index. Phosphor persistence does not apply.
+     */
+    Indexed8,
+
+    /**
+     * 4 bytes per pixel: red, green, blue, alpha
+     */
+    Rgba8888,
+
+    /**
+     * 2 bytes per pixel, little endian, with 5 bits of red, 6 of green and 5 of blue from the most significant bit
+     */
+    Rgb565,
+}
+
+impl PixelFormat {
+    /**
+     * Size in bytes of a buffer holding the given number of pixels
+     */
+    pub fn buffer_size (&self, pixels: usize) -> usize {
+        match self {
+            PixelFormat::Packed1    =>  pixels.div_ceil(8),
+            PixelFormat::Indexed8   =>  pixels,
+            PixelFormat::Rgba8888   =>  pixels * 4,
+            PixelFormat::Rgb565     =>  pixels * 2,
+        }
+    }
+}
+
+/**
+ * Color between two others, at a given level from 0 to 1
+ */
+pub fn mix (from: [u8; 4], to: [u8; 4], level: f32) -> [u8; 4] {
+    let mut color = [0; 4];
+
+    for (channel, (&from, &to)) in color.iter_mut().zip(from.iter().zip(to.iter())) {
+        *channel = (from as f32 + (to as f32 - from as f32) * level).round() as u8;
+    }
+
+    color
+}
+
+pub fn rgb565 (color: [u8; 4]) -> u16 {
+    ((color[0] as u16 >> 3) << 11) | ((color[1] as u16 >> 2) << 5) | (color[2] as u16 >> 3)
+}
+
+/**
+ * Persistent framebuffer, so hosts can read it from memory without a copy per frame
+ */
+pub struct Output {
+    pub format: PixelFormat,
+    pub palette: Palette,
+    pub phosphor: Option<Phosphor>,
+    pub buffer: Vec<u8>,
+}
+
+impl Default for Output {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Output {
+    pub fn new () -> Self {
+        Self::with_format(PixelFormat::Rgba8888)
+    }
+
+    pub fn with_format (format: PixelFormat) -> Self {
+        Self {
+            format,
+            palette: Palette::theme(Theme::Classic),
+            phosphor: None,
+            buffer: vec![0; format.buffer_size(VRAM_SIZE)],
+        }
+    }
+
+    /**
+     * Reallocates the buffer for another format
+     */
+    pub fn set_format (&mut self, format: PixelFormat) {
+        self.format = format;
+        self.buffer = vec![0; format.buffer_size(VRAM_SIZE)];
+    }
+
+    pub fn render (&mut self, display: &Display) {
+        let pixels = display.framebuffer.iter();
+
+        match self.format {
+            PixelFormat::Packed1 => {
+                for (byte, chunk) in self.buffer.iter_mut().zip(display.framebuffer.chunks(8)) {
+                    *byte = chunk.iter().enumerate().fold(0, |byte, (bit, &on)| byte | (on as u8) << (7 - bit));
+                }
+            },
+            PixelFormat::Indexed8 => {
+                for (byte, &on) in self.buffer.iter_mut().zip(pixels) {
+                    *byte = on as u8;
+                }
+            },
+            PixelFormat::Rgba8888 => render_rgba(display, &self.palette, self.phosphor.as_ref(), &mut self.buffer),
+            PixelFormat::Rgb565 => {
+                for (index, (pixel, &on)) in self.buffer.chunks_mut(2).zip(pixels).enumerate() {
+                    pixel.copy_from_slice(&rgb565(color(display, &self.palette, self.phosphor.as_ref(), index, on)).to_le_bytes());
+                }
+            },
+        }
+    }
+}
+
+/**
+ * On CHIP-8X, the palette comes from the color attributes of the pixel instead
+ */
+fn color (display: &Display, palette: &Palette, phosphor: Option<&Phosphor>, index: usize, on: bool) -> [u8; 4] {
+    let palette = match &display.colors {
+        Some(colors)    =>  &colors.palette(index),
+        None            =>  palette,
+    };
+
+    match phosphor {
+        Some(phosphor)  =>  mix(palette.rgba(0), palette.rgba(1), phosphor.level(index, on)),
+        None            =>  palette.rgba(on as u8),
+    }
+}
+
+pub fn render_rgba (display: &Display, palette: &Palette, phosphor: Option<&Phosphor>, buffer: &mut [u8]) {
+    for (index, (pixel, &on)) in buffer.chunks_mut(4).zip(display.framebuffer.iter()).enumerate() {
+        pixel.copy_from_slice(&color(display, palette, phosphor, index, on));
+    }
+}
+
+#[test]
+fn formats () {
+    let mut display = Display::new();
+
+    display.draw_sprite(0, 0, &[0b1010_0000], true);
+
+    let mut output = Output::with_format(PixelFormat::Packed1);
+    output.render(&display);
+    assert_eq!(output.buffer.len(), 256);
+    assert_eq!(&output.buffer[..2], &[0b1010_0000, 0]);
+
+    let mut output = Output::with_format(PixelFormat::Indexed8);
+    output.render(&display);
+    assert_eq!(&output.buffer[..3], &[1, 0, 1]);
+
+    let mut output = Output::with_format(PixelFormat::Rgb565);
+    output.render(&display);
+    assert_eq!(&output.buffer[..4], &[0xFF, 0xFF, 0x00, 0x00]);
+}
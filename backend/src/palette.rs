@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use crate::{
+    util::sha1::sha1_hex,
+    Emulator,
+};
+
+/**
+ * Display colors, as 0xRRGGBB.
+ * XO-CHIP draws on two planes, so a pixel may be set on the first, the second or both.
+ */
+#[wasm_bindgen]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub background: u32,
+
+    /**
+     * Pixels set on the first plane, which is the only one CHIP-8 draws on
+     */
+    pub foreground: u32,
+
+    /**
+     * Pixels set on the second plane
+     */
+    pub plane2: u32,
+
+    /**
+     * Pixels set on both planes
+     */
+    pub blend: u32,
+}
+
+/**
+ * Built-in palettes
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Theme {
+    /**
+     * White on black
+     */
+    Classic,
+
+    /**
+     * Colors of the Octo IDE
+     */
+    Octo,
+    LcdGreen,
+    AmberCrt,
+}
+
+#[wasm_bindgen]
+impl Palette {
+    pub fn new (background: u32, foreground: u32, plane2: u32, blend: u32) -> Self {
+        Self { background, foreground, plane2, blend }
+    }
+
+    pub fn theme (theme: Theme) -> Self {
+        match theme {
+            Theme::Classic  =>  Self::new(0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555),
+            Theme::Octo     =>  Self::new(0x996600, 0xFFCC00, 0xFF6600, 0x662200),
+            Theme::LcdGreen =>  Self::new(0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F),
+            Theme::AmberCrt =>  Self::new(0x1A1000, 0xFFB000, 0xB37B00, 0x664600),
+        }
+    }
+}
+
+impl Palette {
+    /**
+     * RGBA color of a pixel, indexed by its planes: bit 0 for the first one, bit 1 for the second one
+     */
+    pub fn rgba (&self, index: u8) -> [u8; 4] {
+        let color = match index & 0x3 {
+            0   =>  self.background,
+            1   =>  self.foreground,
+            2   =>  self.plane2,
+            _   =>  self.blend,
+        };
+
+        [(color >> 16) as u8, (color >> 8) as u8, color as u8, 0xFF]
+    }
+}
+
+/**
+ * Palette in use, and the ones chosen for specific ROMs, keyed by their SHA-1
+ */
+pub struct Palettes {
+    pub default: Palette,
+    pub overrides: HashMap<String, Palette>,
+}
+
+impl Default for Palettes {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Palettes {
+    pub fn new () -> Self {
+        Self {
+            default: Palette::theme(Theme::Classic),
+            overrides: HashMap::new(),
+        }
+    }
+
+    pub fn get (&self, rom: &[u8]) -> Palette {
+        match self.overrides.get(&sha1_hex(rom)) {
+            Some(palette)   =>  *palette,
+            None            =>  self.default,
+        }
+    }
+}
+
+impl Emulator {
+    /**
+     * Picks the palette to render with, after the default or overrides changed
+     */
+    pub (crate) fn update_palette (&mut self) {
+        self.output.palette = self.palettes.get(&self.rom);
+        self.output.render(&self.display);
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Palette used to render the current ROM
+     */
+    pub fn palette (&self) -> Palette {
+        self.output.palette
+    }
+
+    /**
+     * Palette used by ROMs without an override
+     */
+    pub fn set_palette (&mut self, palette: Palette) {
+        self.palettes.default = palette;
+        self.update_palette();
+    }
+
+    pub fn set_theme (&mut self, theme: Theme) {
+        self.set_palette(Palette::theme(theme));
+    }
+
+    /**
+     * Uses a palette whenever the ROM with this SHA-1 is loaded
+     */
+    pub fn palette_override (&mut self, rom: &str, palette: Palette) {
+        self.palettes.overrides.insert(rom.to_lowercase(), palette);
+        self.update_palette();
+    }
+
+    pub fn palette_override_remove (&mut self, rom: &str) {
+        self.palettes.overrides.remove(&rom.to_lowercase());
+        self.update_palette();
+    }
+
+    /**
+     * Overrides as JSON, for the host to persist them
+     */
+    pub fn palette_overrides_export (&self) -> String {
+        serde_json::to_string(&self.palettes.overrides).unwrap()
+    }
+
+    pub fn palette_overrides_import (&mut self, json: &str) -> Result<(), JsValue> {
+        let overrides: HashMap<String, Palette> = serde_json::from_str(json).map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        self.palettes.overrides.extend(overrides.into_iter().map(|(rom, palette)| (rom.to_lowercase(), palette)));
+        self.update_palette();
+        Ok(())
+    }
+}
+
+#[test]
+fn overrides () {
+    let rom = [0x12, 0x00];
+    let mut emulator = Emulator::new(&rom).unwrap();
+
+    emulator.set_theme(Theme::Octo);
+    assert_eq!(emulator.palette(), Palette::theme(Theme::Octo));
+    assert_eq!(emulator.palette().rgba(1), [0xFF, 0xCC, 0x00, 0xFF]);
+
+    emulator.palette_override(&sha1_hex(&rom).to_uppercase(), Palette::theme(Theme::AmberCrt));
+    assert_eq!(emulator.palette(), Palette::theme(Theme::AmberCrt));
+
+    let json = emulator.palette_overrides_export();
+    emulator.palette_override_remove(&sha1_hex(&rom));
+    assert_eq!(emulator.palette(), Palette::theme(Theme::Octo));
+
+    emulator.palette_overrides_import(&json).unwrap();
+    assert_eq!(emulator.palette(), Palette::theme(Theme::AmberCrt));
+}
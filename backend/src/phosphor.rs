@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+use crate::{
+    display::{Display, VRAM_SIZE},
+    Emulator,
+};
+
+/**
+ * Simulates the persistence of a CRT, so that sprites erased and redrawn with XOR do not flicker.
+ * Each pixel gets an intensity between 0 (background) and 1 (foreground), updated once per 60Hz frame.
+ */
+pub enum Phosphor {
+    /**
+     * Lit pixels are at full intensity, then fade by the decay factor every frame
+     */
+    Decay {
+        decay: f32,
+        intensity: Vec<f32>,
+    },
+
+    /**
+     * Intensity is the share of the last frames in which the pixel was lit
+     */
+    Blend {
+        frames: usize,
+        history: VecDeque<Box<[bool; VRAM_SIZE]>>,
+    },
+}
+
+impl Phosphor {
+    pub fn decay (decay: f32) -> Self {
+        Phosphor::Decay {
+            decay,
+            intensity: vec![0.0; VRAM_SIZE],
+        }
+    }
+
+    pub fn blend (frames: usize) -> Self {
+        Phosphor::Blend {
+            frames,
+            history: VecDeque::with_capacity(frames),
+        }
+    }
+
+    /**
+     * Accounts for a new frame
+     */
+    pub fn update (&mut self, display: &Display) {
+        match self {
+            Phosphor::Decay { decay, intensity } => {
+                for (level, &on) in intensity.iter_mut().zip(display.framebuffer.iter()) {
+                    *level = if on { 1.0 } else { *level * *decay };
+                }
+            },
+            Phosphor::Blend { frames, history } => {
+                if history.len() == *frames {
+                    history.pop_front();
+                }
+                history.push_back(Box::new(display.framebuffer));
+            },
+        }
+    }
+
+    /**
+     * Intensity of a pixel, given whether it is currently lit
+     */
+    pub fn level (&self, index: usize, on: bool) -> f32 {
+        match self {
+            Phosphor::Decay { intensity, .. }   =>  intensity[index].max(on as u8 as f32),
+            Phosphor::Blend { history, .. }     =>  {
+                if history.is_empty() {
+                    return on as u8 as f32;
+                }
+
+                history.iter().filter(|frame| frame[index]).count() as f32 / history.len() as f32
+            },
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Fades pixels out instead of turning them off at once.
+     * `decay` is the share of intensity kept from one frame to the next, between 0 and 1.
+     */
+    pub fn set_phosphor_decay (&mut self, decay: f32) {
+        if !(0.0..1.0).contains(&decay) {
+            log::warn!("Ignoring invalid phosphor decay {}", decay);
+            return;
+        }
+
+        self.output.phosphor = Some(Phosphor::decay(decay));
+    }
+
+    /**
+     * Averages the last frames together
+     */
+    pub fn set_phosphor_blend (&mut self, frames: usize) {
+        if frames == 0 {
+            log::warn!("Ignoring invalid phosphor blend of {} frames", frames);
+            return;
+        }
+
+        self.output.phosphor = Some(Phosphor::blend(frames));
+    }
+
+    pub fn clear_phosphor (&mut self) {
+        self.output.phosphor = None;
+    }
+}
+
+#[test]
+fn persistence () {
+    let mut display = Display::new();
+    let mut decay = Phosphor::decay(0.5);
+    let mut blend = Phosphor::blend(4);
+
+    display.draw_pixel(0, 0, true);
+    decay.update(&display);
+    blend.update(&display);
+
+    display.draw_pixel(0, 0, false);
+    decay.update(&display);
+    blend.update(&display);
+
+    assert_eq!(decay.level(0, false), 0.5);
+    assert_eq!(blend.level(0, false), 0.5);
+    assert_eq!(decay.level(1, true), 1.0);
+
+    for _ in 0..3 {
+        blend.update(&display);
+    }
+    assert_eq!(blend.level(0, false), 0.0);
+}
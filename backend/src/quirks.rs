@@ -0,0 +1,81 @@
+use wasm_bindgen::prelude::*;
+
+/**
+ * Behaviors that differ between CHIP-8 interpreters.
+ * Defaults match what this emulator has always done.
+ * https://github.com/chip-8/chip-8-database/blob/master/database/quirks.json
+ */
+#[wasm_bindgen]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Quirks {
+    /**
+     * 8XY6 and 8XYE shift VX in place instead of storing the shifted VY
+     */
+    pub shift: bool,
+
+    /**
+     * FX55 and FX65 increment I by X instead of X + 1
+     */
+    pub memory_increment_by_x: bool,
+
+    /**
+     * FX55 and FX65 leave I unchanged
+     */
+    pub memory_leave_i_unchanged: bool,
+
+    /**
+     * Sprites wrap around the edges of the screen instead of being clipped
+     */
+    pub wrap: bool,
+
+    /**
+     * BNNN jumps to XNN + VX instead of NNN + V0
+     */
+    pub jump: bool,
+
+    /**
+     * 8XY1, 8XY2 and 8XY3 reset VF
+     */
+    pub logic: bool,
+
+    /**
+     * DXYN waits for the next timer decrement (vertical blank) before execution continues
+     */
+    pub vblank: bool,
+
+    /**
+     * FX0A waits for a key to be pressed then released, like the COSMAC VIP, instead of completing on a held key
+     */
+    #[serde(default)]
+    pub key_release: bool,
+
+    /**
+     * 8XY4 to 8XYE write VF after the result instead of before, so the flag is kept when X is F
+     */
+    #[serde(default)]
+    pub flag_last: bool,
+}
+
+impl Default for Quirks {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Quirks {
+    pub fn new () -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            jump: false,
+            logic: false,
+            vblank: false,
+            key_release: false,
+            flag_last: false,
+        }
+    }
+}
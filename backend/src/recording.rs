@@ -0,0 +1,325 @@
+use wasm_bindgen::prelude::*;
+use crate::{
+    audio::{self, Audio},
+    clock::CLOCK_TIMER,
+    display::{DISPLAY_WIDTH, DISPLAY_HEIGHT},
+    scale::{Image, ScaleFilter, ScaleOverlay},
+    util::{gif, wav},
+    Emulator,
+};
+
+/**
+ * Bytes of frames and sound a recording keeps before it stops capturing, about 5 minutes of frames that all differ
+ */
+pub const RECORDING_LIMIT: usize = 192 * 1024 * 1024;
+
+/**
+ * Framebuffer shown for one or more consecutive 60Hz frames
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct RecordedFrame {
+    /**
+     * RGBA
+     */
+    pub pixels: Vec<u8>,
+    pub frames: usize,
+}
+
+/**
+ * Gameplay captured once per 60Hz frame. Identical consecutive frames are stored once.
+ */
+#[wasm_bindgen]
+pub struct Recording {
+    pub (crate) frames: Vec<RecordedFrame>,
+
+    /**
+     * Sound synthesized like host audio, on every tick since recording started
+     */
+    pub (crate) audio: Audio,
+
+    /**
+     * Bytes of distinct frames stored
+     */
+    pixel_bytes: usize,
+    pub (crate) limit: usize,
+    truncated: bool,
+}
+
+impl Recording {
+    pub fn new (audio: Audio) -> Self {
+        Self {
+            frames: Vec::new(),
+            audio,
+            pixel_bytes: 0,
+            limit: RECORDING_LIMIT,
+            truncated: false,
+        }
+    }
+
+    /**
+     * Ignored once the recording reached its size limit
+     */
+    pub fn capture (&mut self, pixels: Vec<u8>) {
+        if self.truncated {
+            return;
+        }
+
+        if self.pixel_bytes + self.audio.buffer.len() * std::mem::size_of::<f32>() >= self.limit {
+            log::warn!("Recording reached its limit of {} bytes, later frames are not captured", self.limit);
+            self.truncated = true;
+            return;
+        }
+
+        match self.frames.last_mut() {
+            Some(last) if last.pixels == pixels =>  last.frames += 1,
+            _                                   =>  {
+                self.pixel_bytes += pixels.len();
+                self.frames.push(RecordedFrame { pixels, frames: 1 });
+            },
+        }
+    }
+
+    /**
+     * Every 60Hz frame, duplicates included
+     */
+    fn expanded (&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.frames.iter().flat_map(|frame| std::iter::repeat_n(&frame.pixels, frame.frames))
+    }
+}
+
+#[wasm_bindgen]
+impl Recording {
+    /**
+     * Number of 60Hz frames recorded
+     */
+    pub fn length (&self) -> usize {
+        self.frames.iter().map(|frame| frame.frames).sum()
+    }
+
+    /**
+     * Whether capture stopped early because the recording reached `RECORDING_LIMIT`
+     */
+    pub fn truncated (&self) -> bool {
+        self.truncated
+    }
+
+    /**
+     * Animated GIF, upscaled by nearest neighbor. Colors past the 256th are replaced by the closest ones.
+     */
+    pub fn gif (&self, factor: usize) -> Vec<u8> {
+        let factor = factor.max(1);
+        let mut palette: Vec<[u8; 3]> = Vec::new();
+        let mut frames = Vec::with_capacity(self.frames.len());
+        let mut elapsed = 0;
+
+        for frame in &self.frames {
+            let image = Image::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, frame.pixels.clone()).scale(ScaleFilter::Nearest, factor, ScaleOverlay::None);
+            let indices = image.pixels.chunks(4).map(|pixel| {
+                let color = [pixel[0], pixel[1], pixel[2]];
+
+                match palette.iter().position(|&entry| entry == color) {
+                    Some(index)                 =>  index as u8,
+                    None if palette.len() < 256 =>  {
+                        palette.push(color);
+                        (palette.len() - 1) as u8
+                    },
+                    None                        =>  closest(&palette, color),
+                }
+            }).collect::<Vec<u8>>();
+
+            for delay in delays(elapsed, frame.frames) {
+                frames.push((indices.clone(), delay));
+            }
+
+            elapsed += frame.frames;
+        }
+
+        let frames = frames.iter().map(|(indices, delay)| gif::Frame { indices, delay: *delay }).collect::<Vec<_>>();
+
+        gif::encode(DISPLAY_WIDTH * factor, DISPLAY_HEIGHT * factor, &palette, &frames)
+    }
+
+    /**
+     * Raw RGBA frames at 60 frames per second, to be read with `ffmpeg -f rawvideo -pixel_format rgba -video_size 64x32 -framerate 60`
+     */
+    pub fn rgba (&self) -> Vec<u8> {
+        self.expanded().flatten().copied().collect()
+    }
+
+    /**
+     * YUV4MPEG2 stream at 60 frames per second, with full chroma
+     */
+    pub fn y4m (&self) -> Vec<u8> {
+        let mut y4m = format!("YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444\n", DISPLAY_WIDTH, DISPLAY_HEIGHT).into_bytes();
+
+        for pixels in self.expanded() {
+            let yuv = pixels.chunks(4).map(|pixel| bt601(pixel[0], pixel[1], pixel[2])).collect::<Vec<_>>();
+
+            y4m.extend_from_slice(b"FRAME\n");
+
+            for plane in 0..3 {
+                y4m.extend(yuv.iter().map(|color| color[plane]));
+            }
+        }
+
+        y4m
+    }
+
+    /**
+     * Sound of the recording as a 16-bit mono WAV file, to be muxed with the video
+     */
+    pub fn wav (&self) -> Vec<u8> {
+        wav::encode(self.audio.sample_rate, &audio::pcm16(&self.audio.buffer.iter().copied().collect::<Vec<f32>>()))
+    }
+}
+
+fn centiseconds (frames: usize) -> usize {
+    (frames as f64 * 100.0 / CLOCK_TIMER).round() as usize
+}
+
+/**
+ * GIF delays of a frame shown after `elapsed` frames. Delays are in hundredths of a second, so rounding errors are carried over to the next frame,
+ * and a delay too long for one GIF frame is split across copies of it.
+ */
+fn delays (elapsed: usize, frames: usize) -> Vec<u16> {
+    let mut delay = centiseconds(elapsed + frames) - centiseconds(elapsed);
+    let mut delays = Vec::with_capacity(1);
+
+    while delay > u16::MAX as usize {
+        delays.push(u16::MAX);
+        delay -= u16::MAX as usize;
+    }
+
+    delays.push(delay as u16);
+    delays
+}
+
+fn closest (palette: &[[u8; 3]], color: [u8; 3]) -> u8 {
+    let distance = |entry: &[u8; 3]| entry.iter().zip(color.iter()).map(|(&a, &b)| (a as i32 - b as i32).pow(2)).sum::<i32>();
+
+    palette.iter().enumerate().min_by_key(|(_, entry)| distance(entry)).map_or(0, |(index, _)| index as u8)
+}
+
+/**
+ * RGB to studio range YCbCr
+ */
+fn bt601 (r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (r as f64, g as f64, b as f64);
+
+    [
+        (16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0).round() as u8,
+        (128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0).round() as u8,
+        (128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0).round() as u8,
+    ]
+}
+
+impl Emulator {
+    /**
+     * Called on every 60Hz frame
+     */
+    pub (crate) fn video_capture (&mut self) {
+        if self.video.is_some() {
+            let pixels = self.get_framebuffer();
+
+            if let Some(video) = &mut self.video {
+                video.capture(pixels);
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Starts capturing every 60Hz frame, along with the sound at the given sample rate, with the host waveform, frequency and volume if set
+     */
+    pub fn video_record_start (&mut self, sample_rate: u32) {
+        if sample_rate == 0 {
+            log::warn!("Ignoring invalid recording sample rate {}", sample_rate);
+            return;
+        }
+
+        self.video = Some(Recording::new(self.audio_export(sample_rate)));
+    }
+
+    pub fn video_record_stop (&mut self) -> Option<Recording> {
+        self.video.take()
+    }
+
+    pub fn video_recording (&self) -> bool {
+        self.video.is_some()
+    }
+}
+
+#[test]
+fn record () {
+    // Draw the 0 glyph on the second frame, then loop
+    let mut emulator = Emulator::new(&[0x60, 0x00, 0xD0, 0x05, 0x12, 0x04]).unwrap();
+
+    emulator.set_instructions_per_frame(1);
+    emulator.video_record_start(44100);
+    for _ in 0..4 {
+        emulator.frame();
+    }
+
+    let recording = emulator.video_record_stop().unwrap();
+    assert_eq!(recording.length(), 4);
+    assert_eq!(recording.frames.iter().map(|frame| frame.frames).collect::<Vec<_>>(), vec![1, 3]);
+    assert_eq!(recording.rgba().len(), 4 * 64 * 32 * 4);
+    assert_eq!(recording.y4m().len(), 37 + 4 * (6 + 64 * 32 * 3));
+    assert_eq!(&recording.gif(1)[..6], gif::SIGNATURE);
+    assert_eq!(recording.wav().len(), 44 + 4 * 735 * 2);
+}
+
+#[test]
+fn record_audio () {
+    // XO-CHIP pattern of alternating bytes of ones and zeroes, played for 4 frames from the second one
+    let rom = [0xA2, 0x0A, 0xF0, 0x02, 0x60, 0x04, 0xF0, 0x18, 0x12, 0x08, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+    let mut emulator = Emulator::new(&rom).unwrap();
+    let mut export = Emulator::new(&rom).unwrap();
+
+    emulator.set_instructions_per_frame(4);
+    emulator.video_record_start(8000);
+    for _ in 0..6 {
+        emulator.frame();
+    }
+
+    export.set_instructions_per_frame(4);
+    let mut recording = emulator.video_record_stop().unwrap();
+    let samples = export.audio_render(6, 8000);
+
+    assert_eq!(recording.audio.buffer.make_contiguous(), samples.as_slice());
+    assert!(samples.contains(&-1.0));
+    assert_eq!(recording.wav(), wav::encode(8000, &audio::pcm16(&samples)));
+}
+
+#[test]
+fn long_delay () {
+    assert_eq!(delays(0, 1), vec![2]);
+    assert_eq!(delays(1, 1), vec![1]);
+
+    // 20 minutes of the same frame last 120000 hundredths of a second
+    assert_eq!(delays(1, 20 * 60 * 60), vec![u16::MAX, 54465]);
+}
+
+#[test]
+fn record_limit () {
+    // Draw V0 as a glyph address offset, increment it, loop, so every frame differs
+    let mut emulator = Emulator::new(&[0x00, 0xE0, 0xF0, 0x29, 0xD1, 0x15, 0x70, 0x01, 0x12, 0x00]).unwrap();
+
+    emulator.set_instructions_per_frame(5);
+    emulator.video_record_start(8000);
+    emulator.video.as_mut().unwrap().limit = 3 * DISPLAY_WIDTH * DISPLAY_HEIGHT * 4;
+    for _ in 0..10 {
+        emulator.frame();
+    }
+
+    let samples = emulator.video.as_ref().unwrap().audio.buffer.len();
+
+    emulator.frame();
+
+    let recording = emulator.video_record_stop().unwrap();
+    assert!(recording.truncated());
+    assert_eq!((recording.length(), recording.frames.len()), (3, 3));
+    assert_eq!(recording.audio.buffer.len(), samples);
+}
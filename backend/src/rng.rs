@@ -0,0 +1,39 @@
+/**
+ * Seedable xorshift64* generator, so that runs can be replayed deterministically
+ */
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new (seed: u64) -> Self {
+        Self {
+            // Xorshift gets stuck on a zero state
+            state: if seed == 0 { 0x9E3779B97F4A7C15 } else { seed },
+        }
+    }
+
+    pub fn next_u64 (&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    pub fn next_u8 (&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
+
+/**
+ * Non-deterministic seed for fresh emulator instances
+ */
+#[cfg(target_arch = "wasm32")]
+pub fn entropy () -> u64 {
+    (js_sys::Math::random() * u64::MAX as f64) as u64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn entropy () -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |duration| duration.as_nanos() as u64)
+}
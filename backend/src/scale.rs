@@ -0,0 +1,188 @@
+use wasm_bindgen::prelude::*;
+use crate::Emulator;
+
+/**
+ * Pixel-art upscaling algorithms
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScaleFilter {
+    /**
+     * Repeats each pixel, by any integer factor
+     */
+    Nearest,
+
+    /**
+     * Rounds diagonal edges, doubling the size. Also known as EPX.
+     * https://www.scale2x.it/algorithm
+     */
+    Scale2x,
+
+    /**
+     * Same as Scale2x, tripling the size
+     */
+    Scale3x,
+}
+
+impl ScaleFilter {
+    /**
+     * Size factor of the scaled image
+     */
+    pub fn factor (&self, factor: usize) -> usize {
+        match self {
+            ScaleFilter::Nearest    =>  factor.max(1),
+            ScaleFilter::Scale2x    =>  2,
+            ScaleFilter::Scale3x    =>  3,
+        }
+    }
+}
+
+/**
+ * Darkens some lines of the scaled image to mimic a screen
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScaleOverlay {
+    None,
+
+    /**
+     * Last row of every source pixel
+     */
+    Scanlines,
+
+    /**
+     * Last row and column of every source pixel
+     */
+    Grid,
+}
+
+/**
+ * RGBA image, row by row
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new (width: usize, height: usize, pixels: Vec<u8>) -> Self {
+        Self { width, height, pixels }
+    }
+
+    /**
+     * `factor` only applies to nearest neighbor, the other filters have their own
+     */
+    pub fn scale (&self, filter: ScaleFilter, factor: usize, overlay: ScaleOverlay) -> Image {
+        let source = self.pixels.chunks(4).map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]]).collect::<Vec<_>>();
+        let factor = filter.factor(factor);
+        let (width, height) = (self.width * factor, self.height * factor);
+        let mut pixels = vec![0; width * height * 4];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let block = match filter {
+                    ScaleFilter::Nearest    =>  vec![source[x + y * self.width]; factor * factor],
+                    ScaleFilter::Scale2x    =>  self.scale2x(&source, x, y),
+                    ScaleFilter::Scale3x    =>  self.scale3x(&source, x, y),
+                };
+
+                for (n, color) in block.iter().enumerate() {
+                    let (column, row) = (n % factor, n / factor);
+                    let darken = factor > 1 && match overlay {
+                        ScaleOverlay::None      =>  false,
+                        ScaleOverlay::Scanlines =>  row == factor - 1,
+                        ScaleOverlay::Grid      =>  row == factor - 1 || column == factor - 1,
+                    };
+                    let offset = ((x * factor + column) + (y * factor + row) * width) * 4;
+
+                    pixels[offset..offset + 4].copy_from_slice(&if darken { [color[0] / 2, color[1] / 2, color[2] / 2, color[3]] } else { *color });
+                }
+            }
+        }
+
+        Image::new(width, height, pixels)
+    }
+
+    /**
+     * Pixel at an offset from (x, y), clamped to the edges
+     */
+    fn neighbor (&self, source: &[[u8; 4]], x: usize, y: usize, dx: isize, dy: isize) -> [u8; 4] {
+        let x = (x as isize + dx).clamp(0, self.width as isize - 1) as usize;
+        let y = (y as isize + dy).clamp(0, self.height as isize - 1) as usize;
+
+        source[x + y * self.width]
+    }
+
+    fn scale2x (&self, source: &[[u8; 4]], x: usize, y: usize) -> Vec<[u8; 4]> {
+        let p = self.neighbor(source, x, y, 0, 0);
+        let a = self.neighbor(source, x, y, 0, -1);
+        let b = self.neighbor(source, x, y, 1, 0);
+        let c = self.neighbor(source, x, y, -1, 0);
+        let d = self.neighbor(source, x, y, 0, 1);
+
+        vec![
+            if c == a && c != d && a != b { a } else { p },
+            if a == b && a != c && b != d { b } else { p },
+            if d == c && d != b && c != a { c } else { p },
+            if b == d && b != a && d != c { d } else { p },
+        ]
+    }
+
+    fn scale3x (&self, source: &[[u8; 4]], x: usize, y: usize) -> Vec<[u8; 4]> {
+        let at = |dx, dy| self.neighbor(source, x, y, dx, dy);
+        let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+        let (d, e, f) = (at(-1, 0), at(0, 0), at(1, 0));
+        let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+
+        if b == h || d == f {
+            return vec![e; 9];
+        }
+
+        vec![
+            if d == b { d } else { e },
+            if (d == b && e != c) || (b == f && e != a) { b } else { e },
+            if b == f { f } else { e },
+            if (d == b && e != g) || (d == h && e != a) { d } else { e },
+            e,
+            if (b == f && e != i) || (h == f && e != c) { f } else { e },
+            if d == h { d } else { e },
+            if (d == h && e != i) || (h == f && e != g) { h } else { e },
+            if h == f { f } else { e },
+        ]
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Upscaled copy of the framebuffer in RGBA, for screenshots and videos.
+     * Its dimensions are those of the display, multiplied by the factor of the filter.
+     */
+    pub fn get_framebuffer_scaled (&self, filter: ScaleFilter, factor: usize, overlay: ScaleOverlay) -> Vec<u8> {
+        self.screenshot_image(filter, factor, overlay).pixels
+    }
+}
+
+#[test]
+fn filters () {
+    const W: [u8; 4] = [255, 255, 255, 255];
+    const B: [u8; 4] = [0, 0, 0, 255];
+
+    // Diagonal line
+    let image = Image::new(2, 2, [W, B, B, W].concat());
+
+    let nearest = image.scale(ScaleFilter::Nearest, 3, ScaleOverlay::Scanlines);
+    assert_eq!((nearest.width, nearest.height), (6, 6));
+    assert_eq!(&nearest.pixels[..4], &W);
+    assert_eq!(&nearest.pixels[2 * 6 * 4..2 * 6 * 4 + 4], &[127, 127, 127, 255]);
+
+    // Corners between the two pixels get filled in
+    let scaled = image.scale(ScaleFilter::Scale2x, 1, ScaleOverlay::None);
+    assert_eq!(scaled.pixels.chunks(4).take(4).collect::<Vec<_>>(), vec![&W, &W, &B, &B]);
+    assert_eq!(&scaled.pixels[(2 + 4) * 4..(2 + 4) * 4 + 4], &W);
+
+    let scaled = image.scale(ScaleFilter::Scale3x, 1, ScaleOverlay::Grid);
+    assert_eq!((scaled.width, scaled.height), (6, 6));
+}
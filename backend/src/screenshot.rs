@@ -0,0 +1,37 @@
+use wasm_bindgen::prelude::*;
+use crate::{
+    display::{DISPLAY_WIDTH, DISPLAY_HEIGHT},
+    scale::{Image, ScaleFilter, ScaleOverlay},
+    util::png,
+    Emulator,
+};
+
+impl Emulator {
+    /**
+     * Framebuffer with the active palette and phosphor, upscaled
+     */
+    pub fn screenshot_image (&self, filter: ScaleFilter, factor: usize, overlay: ScaleOverlay) -> Image {
+        Image::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, self.get_framebuffer()).scale(filter, factor, overlay)
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /**
+     * Framebuffer as a PNG file, pixel for pixel
+     */
+    pub fn screenshot (&self, filter: ScaleFilter, factor: usize, overlay: ScaleOverlay) -> Vec<u8> {
+        let image = self.screenshot_image(filter, factor, overlay);
+
+        png::encode(image.width, image.height, &image.pixels)
+    }
+}
+
+#[test]
+fn screenshot () {
+    let emulator = Emulator::new(&[0x12, 0x00]).unwrap();
+    let png = emulator.screenshot(ScaleFilter::Nearest, 2, ScaleOverlay::None);
+
+    // Width and height in the header
+    assert_eq!(&png[16..24], &[0, 0, 0, 128, 0, 0, 0, 64]);
+}
@@ -0,0 +1,94 @@
+use wasm_bindgen::prelude::*;
+use crate::Emulator;
+
+/**
+ * What the beeper does while fast-forwarding
+ */
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TurboAudio {
+    Normal,
+    Mute,
+
+    /**
+     * Tone pitched up by the speed factor
+     */
+    Pitch,
+}
+
+/**
+ * Fast-forward: several timer periods are run per host frame, and only some of them are presented
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Turbo {
+    /**
+     * Timer periods run per host frame
+     */
+    pub speed: u32,
+
+    /**
+     * A framebuffer is presented every this many host frames
+     */
+    pub frame_skip: u32,
+    pub audio: TurboAudio,
+}
+
+impl Emulator {
+    /**
+     * Timer periods to run on the next host frame
+     */
+    pub (crate) fn turbo_speed (&self) -> u32 {
+        self.turbo.map_or(1, |turbo| turbo.speed)
+    }
+
+    /**
+     * Counts a host frame and returns whether it should be presented
+     */
+    pub (crate) fn turbo_present (&mut self) -> bool {
+        let frame_skip = self.turbo.map_or(1, |turbo| turbo.frame_skip) as usize;
+
+        self.turbo_frames = (self.turbo_frames + 1) % frame_skip;
+        self.turbo_frames == 0
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    pub fn turbo (&self) -> bool {
+        self.turbo.is_some()
+    }
+
+    pub fn set_turbo (&mut self, speed: u32, frame_skip: u32, audio: TurboAudio) {
+        if speed == 0 || frame_skip == 0 {
+            log::warn!("Ignoring invalid turbo speed {} and frame skip {}", speed, frame_skip);
+            return;
+        }
+
+        self.turbo = Some(Turbo { speed, frame_skip, audio });
+        self.turbo_frames = 0;
+    }
+
+    pub fn clear_turbo (&mut self) {
+        self.turbo = None;
+    }
+
+    /**
+     * Factor to apply to the beeper frequency
+     */
+    pub fn beep_pitch (&self) -> f64 {
+        match self.turbo {
+            Some(Turbo { speed, audio: TurboAudio::Pitch, .. }) =>  speed as f64,
+            _                                                   =>  1.0,
+        }
+    }
+}
+
+#[test]
+fn fast_forward () {
+    let mut emulator = Emulator::new(&[0x12, 0x00]).unwrap();
+
+    emulator.set_turbo(4, 2, TurboAudio::Mute);
+    assert!(!emulator.frame());
+    assert!(emulator.frame());
+    assert_eq!(emulator.frame_count(), 8);
+}
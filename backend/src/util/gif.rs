@@ -0,0 +1,427 @@
+use std::{collections::HashMap, fmt};
+
+/**
+ * Minimal GIF89a encoder for animations with a global color table, and decoder of their pixel indices
+ * https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+ */
+pub const SIGNATURE: &[u8; 6] = b"GIF89a";
+
+/**
+ * Codes are at most 12 bits wide
+ */
+pub const MAX_CODES: usize = 4096;
+
+/**
+ * Largest logical screen, and total of decoded pixels over every image, that is accepted
+ */
+pub const MAX_PIXELS: usize = 1 << 24;
+
+/**
+ * Writes codes of variable width, least significant bit first
+ */
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write (&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish (mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
+
+/**
+ * Variable-length LZW, as used by GIF
+ */
+pub fn lzw_encode (min_code_size: u32, indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = min_code_size + 1;
+
+    writer.write(clear, width);
+
+    let mut indices = indices.iter();
+    let mut prefix = match indices.next() {
+        Some(&index)    =>  index as u16,
+        None            =>  {
+            writer.write(end, width);
+            return writer.finish();
+        },
+    };
+
+    for &index in indices {
+        if let Some(&code) = dictionary.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, width);
+        dictionary.insert((prefix, index), next);
+        next += 1;
+
+        // The decoder widens its codes one entry later than the encoder adds them
+        if next as usize > 1 << width && width < 12 {
+            width += 1;
+        }
+
+        if next as usize == MAX_CODES {
+            writer.write(clear, width);
+            dictionary.clear();
+            next = end + 1;
+            width = min_code_size + 1;
+        }
+
+        prefix = index as u16;
+    }
+
+    writer.write(prefix, width);
+    writer.write(end, width);
+    writer.finish()
+}
+
+/**
+ * Splits data in sub-blocks of up to 255 bytes, followed by a terminator
+ */
+fn sub_blocks (gif: &mut Vec<u8>, data: &[u8]) {
+    for block in data.chunks(255) {
+        gif.push(block.len() as u8);
+        gif.extend_from_slice(block);
+    }
+
+    gif.push(0);
+}
+
+pub struct Frame<'a> {
+    /**
+     * Indices into the palette
+     */
+    pub indices: &'a [u8],
+
+    /**
+     * In hundredths of a second
+     */
+    pub delay: u16,
+}
+
+/**
+ * Encodes an animation that loops forever. The palette holds up to 256 RGB colors.
+ */
+pub fn encode (width: usize, height: usize, palette: &[[u8; 3]], frames: &[Frame]) -> Vec<u8> {
+    let mut gif = SIGNATURE.to_vec();
+
+    // The color table size is a power of two, of at least 2 entries for a minimum code size of 2
+    let bits = (1..=8).find(|bits| 1 << bits >= palette.len()).unwrap_or(8).max(2);
+
+    gif.extend_from_slice(&(width as u16).to_le_bytes());
+    gif.extend_from_slice(&(height as u16).to_le_bytes());
+    gif.extend_from_slice(&[0x80 | 0x70 | (bits as u8 - 1), 0, 0]);
+
+    for n in 0..1 << bits {
+        gif.extend_from_slice(&palette.get(n).copied().unwrap_or_default());
+    }
+
+    // NETSCAPE2.0 application extension, looping forever
+    gif.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    for frame in frames {
+        // Graphic control extension
+        gif.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+        gif.extend_from_slice(&frame.delay.to_le_bytes());
+        gif.extend_from_slice(&[0x00, 0x00]);
+
+        // Image descriptor, covering the whole screen
+        gif.extend_from_slice(&[0x2C, 0, 0, 0, 0]);
+        gif.extend_from_slice(&(width as u16).to_le_bytes());
+        gif.extend_from_slice(&(height as u16).to_le_bytes());
+        gif.push(0);
+
+        gif.push(bits as u8);
+        sub_blocks(&mut gif, &lzw_encode(bits, frame.indices));
+    }
+
+    gif.push(0x3B);
+    gif
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GifError {
+    /**
+     * Not a GIF87a or GIF89a file
+     */
+    Signature,
+
+    /**
+     * File ended in the middle of a block
+     */
+    Truncated,
+
+    /**
+     * Image data refers to a code that is not in the LZW table yet
+     */
+    Code(u16),
+    Block(u8),
+
+    /**
+     * Image is larger than the logical screen, or than MAX_PIXELS
+     */
+    Size(usize, usize),
+}
+
+impl fmt::Display for GifError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GifError::Signature     =>  write!(f, "Not a GIF file"),
+            GifError::Truncated     =>  write!(f, "GIF file is truncated"),
+            GifError::Code(code)    =>  write!(f, "Invalid LZW code {} in GIF image data", code),
+            GifError::Block(block)  =>  write!(f, "Unknown GIF block 0x{:02X}", block),
+            GifError::Size(w, h)    =>  write!(f, "GIF image of {}x{} is out of bounds", w, h),
+        }
+    }
+}
+
+/**
+ * Reads codes of variable width, least significant bit first
+ */
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read (&mut self, width: u32) -> Option<u16> {
+        let mut code = 0;
+
+        for bit in 0..width as usize {
+            let byte = self.bytes.get((self.position + bit) / 8)?;
+            code |= (((byte >> ((self.position + bit) % 8)) & 1) as u16) << bit;
+        }
+
+        self.position += width as usize;
+        Some(code)
+    }
+}
+
+/**
+ * Decodes at most `limit` indices, dropping any further data
+ */
+pub fn lzw_decode (min_code_size: u32, data: &[u8], limit: usize) -> Result<Vec<u8>, GifError> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let root = || (0..clear).map(|index| vec![index as u8]).chain([Vec::new(), Vec::new()]).collect::<Vec<_>>();
+    let mut reader = BitReader { bytes: data, position: 0 };
+    let mut table = root();
+    let mut width = min_code_size + 1;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut indices = Vec::new();
+
+    // Data that stops without an end code is accepted, as many encoders omit it
+    while let Some(code) = reader.read(width) {
+        if indices.len() >= limit {
+            break;
+        } else if code == clear {
+            table = root();
+            width = min_code_size + 1;
+            previous = None;
+            continue;
+        } else if code == end {
+            break;
+        }
+
+        let entry = match (table.get(code as usize), &previous) {
+            (Some(entry), _)                                        =>  entry.clone(),
+            (None, Some(previous)) if code as usize == table.len()  =>  [previous.as_slice(), &previous[..1]].concat(),
+            _                                                       =>  return Err(GifError::Code(code)),
+        };
+
+        if let Some(previous) = previous {
+            if table.len() < MAX_CODES {
+                table.push([previous.as_slice(), &entry[..1]].concat());
+            }
+
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+
+        indices.extend_from_slice(&entry);
+        previous = Some(entry);
+    }
+
+    indices.truncate(limit);
+    Ok(indices)
+}
+
+/**
+ * Image of an animation, in palette indices
+ */
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub indices: Vec<u8>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take (&mut self, length: usize) -> Result<&[u8], GifError> {
+        let bytes = self.bytes.get(self.position .. self.position + length).ok_or(GifError::Truncated)?;
+
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn byte (&mut self) -> Result<u8, GifError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word (&mut self) -> Result<usize, GifError> {
+        let bytes = self.take(2)?;
+
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+    }
+
+    fn sub_blocks (&mut self) -> Result<Vec<u8>, GifError> {
+        let mut data = Vec::new();
+
+        loop {
+            match self.byte()? {
+                0       =>  return Ok(data),
+                length  =>  data.extend_from_slice(self.take(length as usize)?),
+            }
+        }
+    }
+}
+
+/**
+ * Decodes every image of a GIF file, in order, without compositing them
+ */
+pub fn decode (gif: &[u8]) -> Result<Vec<Image>, GifError> {
+    if gif.get(..6) != Some(b"GIF87a") && gif.get(..6) != Some(SIGNATURE) {
+        return Err(GifError::Signature);
+    }
+
+    let mut reader = Reader { bytes: gif, position: 6 };
+    let mut images = Vec::new();
+    let mut pixels = 0;
+
+    let screen_width = reader.word()?;
+    let screen_height = reader.word()?;
+    let flags = reader.byte()?;
+
+    if screen_width * screen_height > MAX_PIXELS {
+        return Err(GifError::Size(screen_width, screen_height));
+    }
+
+    reader.take(2)?;
+
+    if flags & 0x80 != 0 {
+        reader.take(3 << ((flags & 0x07) + 1))?;
+    }
+
+    loop {
+        match reader.byte()? {
+            // Extension, all of which are skipped
+            0x21 => {
+                reader.byte()?;
+                reader.sub_blocks()?;
+            },
+            0x2C => {
+                let left = reader.word()?;
+                let top = reader.word()?;
+                let width = reader.word()?;
+                let height = reader.word()?;
+                let flags = reader.byte()?;
+
+                pixels += width * height;
+
+                if left + width > screen_width || top + height > screen_height || pixels > MAX_PIXELS {
+                    return Err(GifError::Size(width, height));
+                }
+
+                if flags & 0x80 != 0 {
+                    reader.take(3 << ((flags & 0x07) + 1))?;
+                }
+
+                let min_code_size = reader.byte()?.clamp(2, 11) as u32;
+                let mut indices = lzw_decode(min_code_size, &reader.sub_blocks()?, width * height)?;
+
+                indices.resize(width * height, 0);
+
+                // Interlaced rows are stored every 8th row from 0, every 8th from 4, every 4th from 2, then every 2nd from 1
+                if flags & 0x40 != 0 {
+                    let rows = [(0, 8), (4, 8), (2, 4), (1, 2)].iter().flat_map(|&(start, step)| (start..height).step_by(step));
+                    let mut deinterlaced = vec![0; width * height];
+
+                    for (n, row) in rows.enumerate() {
+                        deinterlaced[row * width .. (row + 1) * width].copy_from_slice(&indices[n * width .. (n + 1) * width]);
+                    }
+
+                    indices = deinterlaced;
+                }
+
+                images.push(Image { width, height, indices });
+            },
+            0x3B    =>  return Ok(images),
+            block   =>  return Err(GifError::Block(block)),
+        }
+    }
+}
+
+#[test]
+fn lzw () {
+    // Single pixel image data of the well-known 1x1 GIF
+    assert_eq!(lzw_encode(2, &[0]), vec![0x44, 0x01]);
+}
+
+#[test]
+fn roundtrip () {
+    let indices: Vec<u8> = (0..20000u32).map(|n| (n * n / 7 % 4) as u8).collect();
+    let frames = [Frame { indices: &indices, delay: 2 }, Frame { indices: &indices[..100], delay: 2 }];
+    let images = decode(&encode(100, 200, &[[0, 0, 0]; 4], &frames)).unwrap();
+
+    assert_eq!(lzw_decode(2, &lzw_encode(2, &indices), indices.len()).unwrap(), indices);
+    assert_eq!(lzw_decode(2, &lzw_encode(2, &indices), 10).unwrap(), &indices[..10]);
+    assert_eq!(images.len(), 2);
+    assert_eq!((images[0].width, images[0].height), (100, 200));
+    assert_eq!(images[0].indices, indices);
+    assert_eq!(&images[1].indices[..100], &indices[..100]);
+    assert_eq!(decode(b"PNG").err(), Some(GifError::Signature));
+}
+
+#[test]
+fn bounds () {
+    let mut gif = encode(2, 2, &[[0, 0, 0]; 4], &[Frame { indices: &[0; 4], delay: 2 }]);
+    let descriptor = gif.iter().position(|&byte| byte == 0x2C).unwrap();
+
+    // Image declaring 65535x65535 pixels in a 2x2 screen
+    gif[descriptor + 5 .. descriptor + 9].copy_from_slice(&[0xFF; 4]);
+    assert_eq!(decode(&gif).err(), Some(GifError::Size(65535, 65535)));
+
+    // Screen declaring 65535x65535 pixels
+    gif[6..10].copy_from_slice(&[0xFF; 4]);
+    assert_eq!(decode(&gif).err(), Some(GifError::Size(65535, 65535)));
+}
@@ -1,2 +1,6 @@
 pub mod log;
 pub mod panic;
+pub mod sha1;
+pub mod png;
+pub mod gif;
+pub mod wav;
@@ -0,0 +1,95 @@
+/**
+ * Minimal PNG encoder for RGBA images. Data is stored uncompressed, which is fine for small screenshots.
+ * http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
+ */
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/**
+ * Maximum length of a stored deflate block
+ */
+const BLOCK_SIZE: usize = 0xFFFF;
+
+pub fn crc32 (data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 })
+    })
+}
+
+pub fn adler32 (data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1, 0), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+
+    (b << 16) | a
+}
+
+/**
+ * zlib stream made of stored (uncompressed) deflate blocks
+ */
+pub fn zlib_stored (data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks = data.chunks(BLOCK_SIZE).collect::<Vec<_>>();
+
+    if blocks.is_empty() {
+        stream.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    for (n, block) in blocks.iter().enumerate() {
+        let length = block.len() as u16;
+
+        stream.push((n == blocks.len() - 1) as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn chunk (png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let start = png.len() + 4;
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/**
+ * Encodes RGBA pixels, row by row
+ */
+pub fn encode (width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut png = SIGNATURE.to_vec();
+    let mut header = Vec::with_capacity(13);
+    let mut scanlines = Vec::with_capacity((width * 4 + 1) * height);
+
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, truecolor with alpha, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    for row in pixels.chunks(width * 4).take(height) {
+        // No filter
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[test]
+fn checksums () {
+    assert_eq!(crc32(b"IEND"), 0xAE426082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+
+    let png = encode(1, 1, &[255, 0, 0, 255]);
+    assert_eq!(&png[..8], &SIGNATURE);
+    assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+}
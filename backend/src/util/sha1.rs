@@ -0,0 +1,69 @@
+/**
+ * SHA-1 digest, used to identify ROMs
+ * https://datatracker.ietf.org/doc/html/rfc3174
+ */
+pub fn sha1 (data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+
+    // Pad to a multiple of 64 bytes, ending with the message length in bits
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19  =>  ((b & c) | (!b & d), 0x5A827999),
+                20..=39 =>  (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 =>  ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _       =>  (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+
+    for (bytes, state) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&state.to_be_bytes());
+    }
+
+    digest
+}
+
+/**
+ * Lowercase hexadecimal SHA-1 digest
+ */
+pub fn sha1_hex (data: &[u8]) -> String {
+    sha1(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn digest () {
+    assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+}
@@ -0,0 +1,32 @@
+/**
+ * Encodes mono 16-bit PCM samples to a WAV file
+ * http://soundfile.sapp.org/doc/WaveFormat/
+ */
+pub fn encode (sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+    let size = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + size as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, mono
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    // Byte rate, block alignment and bits per sample
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&size.to_le_bytes());
+
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
@@ -0,0 +1,378 @@
+/**
+ * What the CDP1802 sees of the machine around it
+ */
+pub trait Bus {
+    fn read (&mut self, address: u16) -> u8;
+    fn write (&mut self, address: u16, value: u8);
+
+    /**
+     * OUT 1-7
+     */
+    fn output (&mut self, port: u8, value: u8);
+
+    /**
+     * INP 1-7
+     */
+    fn input (&mut self, port: u8) -> u8;
+
+    /**
+     * External flags EF1-EF4, numbered from 1
+     */
+    fn flag (&mut self, n: u8) -> bool;
+}
+
+/**
+ * RCA CDP1802 COSMAC microprocessor
+ * https://www.cosmacelf.com/publications/data-sheets/cdp1802.pdf
+ */
+pub struct Cdp1802 {
+    /**
+     * Scratchpad registers
+     */
+    pub r: [u16; 16],
+
+    /**
+     * Designates the program counter register
+     */
+    pub p: u8,
+
+    /**
+     * Designates the data pointer register
+     */
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+
+    /**
+     * X and P saved on interrupt
+     */
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+
+    /**
+     * Set by IDL until the next DMA or interrupt
+     */
+    pub idle: bool,
+}
+
+impl Default for Cdp1802 {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Cdp1802 {
+    pub fn new () -> Self {
+        Self {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    /**
+     * Runs one instruction and returns the number of machine cycles it took
+     */
+    pub fn step (&mut self, bus: &mut impl Bus) -> u32 {
+        if self.idle {
+            return 1;
+        }
+
+        let opcode = self.fetch(bus);
+        let (i, n) = (opcode >> 4, (opcode & 0xF) as usize);
+        let x = self.x as usize;
+
+        match (i, n) {
+            (0x0, 0)        =>  self.idle = true,
+            (0x0, _)        =>  self.d = bus.read(self.r[n]),
+            (0x1, _)        =>  self.r[n] = self.r[n].wrapping_add(1),
+            (0x2, _)        =>  self.r[n] = self.r[n].wrapping_sub(1),
+            (0x3, _)        =>  {
+                let condition = self.condition(n & 0x7, bus) != (n & 0x8 != 0);
+                self.branch_short(condition, bus);
+            },
+            (0x4, _)        =>  {
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            },
+            (0x5, _)        =>  bus.write(self.r[n], self.d),
+            (0x6, 0)        =>  self.r[x] = self.r[x].wrapping_add(1),
+            (0x6, 1..=7)    =>  {
+                let value = bus.read(self.r[x]);
+                bus.output(n as u8, value);
+                self.r[x] = self.r[x].wrapping_add(1);
+            },
+            // Reserved for the CDP1804 extended instruction set
+            (0x6, 8)        =>  {},
+            (0x6, _)        =>  {
+                self.d = bus.input(n as u8 - 8);
+                bus.write(self.r[x], self.d);
+            },
+            (0x7, 0x0)      =>  self.ret(true, bus),
+            (0x7, 0x1)      =>  self.ret(false, bus),
+            (0x7, 0x2)      =>  {
+                self.d = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+            },
+            (0x7, 0x3)      =>  {
+                bus.write(self.r[x], self.d);
+                self.r[x] = self.r[x].wrapping_sub(1);
+            },
+            (0x7, 0x4)      =>  self.add(bus.read(self.r[x]), self.df),
+            (0x7, 0x5)      =>  self.subtract(bus.read(self.r[x]), self.d, self.df),
+            (0x7, 0x6)      =>  {
+                let carry = self.d & 1 == 1;
+                self.d = (self.d >> 1) | if self.df { 0x80 } else { 0 };
+                self.df = carry;
+            },
+            (0x7, 0x7)      =>  self.subtract(self.d, bus.read(self.r[x]), self.df),
+            (0x7, 0x8)      =>  bus.write(self.r[x], self.t),
+            (0x7, 0x9)      =>  {
+                self.t = (self.x << 4) | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            },
+            (0x7, 0xA)      =>  self.q = false,
+            (0x7, 0xB)      =>  self.q = true,
+            (0x7, 0xC)      =>  {
+                let value = self.fetch(bus);
+                self.add(value, self.df);
+            },
+            (0x7, 0xD)      =>  {
+                let value = self.fetch(bus);
+                self.subtract(value, self.d, self.df);
+            },
+            (0x7, 0xE)      =>  {
+                let carry = self.d >> 7 == 1;
+                self.d = (self.d << 1) | if self.df { 1 } else { 0 };
+                self.df = carry;
+            },
+            (0x7, _)        =>  {
+                let value = self.fetch(bus);
+                self.subtract(self.d, value, self.df);
+            },
+            (0x8, _)        =>  self.d = self.r[n] as u8,
+            (0x9, _)        =>  self.d = (self.r[n] >> 8) as u8,
+            (0xA, _)        =>  self.r[n] = (self.r[n] & 0xFF00) | self.d as u16,
+            (0xB, _)        =>  self.r[n] = (self.r[n] & 0x00FF) | (self.d as u16) << 8,
+            (0xC, _)        =>  return self.long(n, bus),
+            (0xD, _)        =>  self.p = n as u8,
+            (0xE, _)        =>  self.x = n as u8,
+            (0xF, 0x0)      =>  self.d = bus.read(self.r[x]),
+            (0xF, 0x1)      =>  self.d |= bus.read(self.r[x]),
+            (0xF, 0x2)      =>  self.d &= bus.read(self.r[x]),
+            (0xF, 0x3)      =>  self.d ^= bus.read(self.r[x]),
+            (0xF, 0x4)      =>  self.add(bus.read(self.r[x]), false),
+            (0xF, 0x5)      =>  self.subtract(bus.read(self.r[x]), self.d, true),
+            (0xF, 0x6)      =>  {
+                self.df = self.d & 1 == 1;
+                self.d >>= 1;
+            },
+            (0xF, 0x7)      =>  self.subtract(self.d, bus.read(self.r[x]), true),
+            (0xF, 0x8)      =>  self.d = self.fetch(bus),
+            (0xF, 0x9)      =>  self.d |= self.fetch(bus),
+            (0xF, 0xA)      =>  self.d &= self.fetch(bus),
+            (0xF, 0xB)      =>  self.d ^= self.fetch(bus),
+            (0xF, 0xC)      =>  {
+                let value = self.fetch(bus);
+                self.add(value, false);
+            },
+            (0xF, 0xD)      =>  {
+                let value = self.fetch(bus);
+                self.subtract(value, self.d, true);
+            },
+            (0xF, 0xE)      =>  {
+                self.df = self.d >> 7 == 1;
+                self.d <<= 1;
+            },
+            (..)            =>  {
+                let value = self.fetch(bus);
+                self.subtract(self.d, value, true);
+            },
+        }
+
+        2
+    }
+
+    /**
+     * Acknowledges an interrupt request if interrupts are enabled
+     */
+    pub fn interrupt (&mut self) -> bool {
+        if !self.ie {
+            return false;
+        }
+
+        self.t = (self.x << 4) | self.p;
+        self.x = 2;
+        self.p = 1;
+        self.ie = false;
+        self.idle = false;
+        true
+    }
+
+    /**
+     * DMA out cycle: the byte at R0 is handed to a peripheral
+     */
+    pub fn dma_out (&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.r[0]);
+
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    fn fetch (&mut self, bus: &mut impl Bus) -> u8 {
+        let p = self.p as usize;
+        let value = bus.read(self.r[p]);
+
+        self.r[p] = self.r[p].wrapping_add(1);
+        value
+    }
+
+    /**
+     * Branch conditions, as encoded in the low 3 bits of branch and skip opcodes
+     */
+    fn condition (&mut self, n: usize, bus: &mut impl Bus) -> bool {
+        match n {
+            0   =>  true,
+            1   =>  self.q,
+            2   =>  self.d == 0,
+            3   =>  self.df,
+            _   =>  bus.flag(n as u8 - 3),
+        }
+    }
+
+    fn branch_short (&mut self, condition: bool, bus: &mut impl Bus) {
+        let p = self.p as usize;
+
+        if condition {
+            let target = bus.read(self.r[p]);
+            self.r[p] = (self.r[p] & 0xFF00) | target as u16;
+        } else {
+            self.r[p] = self.r[p].wrapping_add(1);
+        }
+    }
+
+    /**
+     * Long branches and skips, which take 3 machine cycles
+     */
+    fn long (&mut self, n: usize, bus: &mut impl Bus) -> u32 {
+        let p = self.p as usize;
+
+        match n {
+            // NOP
+            0x4 => {},
+            // Long skips
+            0x5 | 0x6 | 0x7 | 0xC | 0xD | 0xE | 0xF => {
+                let condition = match n {
+                    0x5 =>  !self.q,
+                    0x6 =>  self.d != 0,
+                    0x7 =>  !self.df,
+                    0xC =>  self.ie,
+                    0xD =>  self.q,
+                    0xE =>  self.d == 0,
+                    _   =>  self.df,
+                };
+
+                if condition {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            },
+            // Long branches, and LSKP (0xC8) which never branches
+            _ => {
+                let condition = match n & 0x7 {
+                    0   =>  n == 0x0,
+                    1   =>  self.q,
+                    2   =>  self.d == 0,
+                    _   =>  self.df,
+                } != (n & 0x8 != 0 && n != 0x8);
+
+                if condition {
+                    let high = bus.read(self.r[p]);
+                    let low = bus.read(self.r[p].wrapping_add(1));
+                    self.r[p] = (high as u16) << 8 | low as u16;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            },
+        }
+
+        3
+    }
+
+    fn ret (&mut self, enable: bool, bus: &mut impl Bus) {
+        let x = self.x as usize;
+        let value = bus.read(self.r[x]);
+
+        self.r[x] = self.r[x].wrapping_add(1);
+        self.x = value >> 4;
+        self.p = value & 0xF;
+        self.ie = enable;
+    }
+
+    fn add (&mut self, value: u8, carry: bool) {
+        let sum = self.d as u16 + value as u16 + carry as u16;
+
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /**
+     * DF is set when no borrow occurs
+     */
+    fn subtract (&mut self, minuend: u8, subtrahend: u8, no_borrow: bool) {
+        let difference = minuend as i16 - subtrahend as i16 - if no_borrow { 0 } else { 1 };
+
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+}
+
+#[cfg(test)]
+struct TestBus {
+    ram: [u8; 256],
+}
+
+#[cfg(test)]
+impl Bus for TestBus {
+    fn read (&mut self, address: u16) -> u8 { self.ram[address as usize & 0xFF] }
+    fn write (&mut self, address: u16, value: u8) { self.ram[address as usize & 0xFF] = value }
+    fn output (&mut self, _port: u8, _value: u8) {}
+    fn input (&mut self, _port: u8) -> u8 { 0 }
+    fn flag (&mut self, _n: u8) -> bool { false }
+}
+
+#[test]
+fn instructions () {
+    let mut bus = TestBus { ram: [0; 256] };
+    let mut cpu = Cdp1802::new();
+    let program = [
+        0xF8, 0x80, // LDI 80
+        0xA3,       // PLO R3
+        0xE3,       // SEX R3
+        0xF8, 0x90, // LDI 90
+        0x53,       // STR R3
+        0xF4,       // ADD
+        0x3B, 0x20, // BNF 20
+        0xFE,       // SHL
+        0xC0, 0x00, 0x40, // LBR 0040
+    ];
+
+    bus.ram[..program.len()].copy_from_slice(&program);
+
+    let cycles = (0..9).map(|_| cpu.step(&mut bus)).sum::<u32>();
+
+    assert_eq!(cycles, 19);
+    assert_eq!(bus.ram[0x80], 0x90);
+    assert_eq!(cpu.d, 0x40);
+    assert!(!cpu.df);
+    assert_eq!(cpu.r[0], 0x40);
+}
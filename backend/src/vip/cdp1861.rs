@@ -0,0 +1,92 @@
+use crate::display::{Display, DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+/**
+ * Scan lines per frame, including blanking
+ */
+pub const LINES: u32 = 262;
+
+/**
+ * Machine cycles per scan line
+ */
+pub const LINE_CYCLES: u32 = 14;
+pub const FRAME_CYCLES: u32 = LINES * LINE_CYCLES;
+
+/**
+ * Interrupt is requested two lines before the first displayed line
+ */
+pub const INTERRUPT_LINE: u32 = 78;
+pub const DISPLAY_START: u32 = 80;
+pub const DISPLAY_LINES: u32 = 128;
+
+/**
+ * Bytes fetched through DMA per displayed line
+ */
+pub const DMA_BYTES: u32 = 8;
+
+/**
+ * RCA CDP1861 "Pixie" video display controller
+ * https://www.cosmacelf.com/publications/data-sheets/cdp1861.pdf
+ */
+pub struct Cdp1861 {
+    pub enabled: bool,
+
+    /**
+     * Machine cycles elapsed in the current frame
+     */
+    pub cycle: u32,
+
+    /**
+     * Interrupt request raised before the display area, held until the CPU takes it
+     */
+    pub interrupt: bool,
+}
+
+impl Default for Cdp1861 {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Cdp1861 {
+    pub fn new () -> Self {
+        Self {
+            enabled: false,
+            cycle: 0,
+            interrupt: false,
+        }
+    }
+
+    pub fn line (&self) -> u32 {
+        self.cycle / LINE_CYCLES
+    }
+
+    /**
+     * EF1 signals the four lines before the display area starts and ends
+     */
+    pub fn ef1 (&self) -> bool {
+        let line = self.line();
+
+        (DISPLAY_START - 4 .. DISPLAY_START).contains(&line) || (DISPLAY_START + DISPLAY_LINES - 4 .. DISPLAY_START + DISPLAY_LINES).contains(&line)
+    }
+
+    /**
+     * Whether a line is fetched through DMA
+     */
+    pub fn displays (&self, line: u32) -> bool {
+        self.enabled && (DISPLAY_START .. DISPLAY_START + DISPLAY_LINES).contains(&line)
+    }
+
+    /**
+     * Draws a DMA byte. The 128 lines are shown at 32 lines of resolution: CHIP-8 repeats each row four times.
+     */
+    pub fn draw (&self, display: &mut Display, line: u32, offset: u32, byte: u8) {
+        let repeat = DISPLAY_LINES as usize / DISPLAY_HEIGHT;
+        let (y, repetition) = ((line - DISPLAY_START) as usize / repeat, (line - DISPLAY_START) as usize % repeat);
+
+        if repetition == 0 {
+            for bit in 0..8 {
+                display.framebuffer[y * DISPLAY_WIDTH + offset as usize * 8 + bit] = (byte >> (7 - bit)) & 1 == 1;
+            }
+        }
+    }
+}
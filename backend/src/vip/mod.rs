@@ -0,0 +1,286 @@
+mod cdp1802;
+mod cdp1861;
+
+pub use cdp1802::*;
+pub use cdp1861::*;
+
+use wasm_bindgen::prelude::*;
+use crate::{
+    memory::{Memory, PROGRAM_START},
+    display::Display,
+    input::Keypad,
+    Emulator,
+};
+
+/**
+ * Address of the monitor ROM, mirrored up to 0xFFFF
+ */
+pub const MONITOR_START: u16 = 0x8000;
+pub const MONITOR_SIZE: usize = 0x200;
+
+/**
+ * Which machine runs the ROM
+ */
+#[wasm_bindgen]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Machine {
+    /**
+     * High-level CHIP-8 interpreter
+     */
+    Chip8,
+
+    /**
+     * COSMAC VIP running the original CHIP-8 interpreter
+     */
+    CosmacVip,
+
+    /**
+     * High-level CHIP-8X interpreter, with the VP-590 color board and a second keypad
+     */
+    Chip8X,
+}
+
+/**
+ * COSMAC VIP, with 4K of RAM at 0x0000 holding the interpreter and the program
+ */
+pub struct Vip {
+    pub cpu: Cdp1802,
+    pub video: Cdp1861,
+
+    /**
+     * Key selected by OUT 2, reported on EF3
+     */
+    pub key: u8,
+    pub frames: usize,
+    pub cycles: usize,
+    interpreter: Vec<u8>,
+    monitor: Vec<u8>,
+}
+
+struct VipBus<'a> {
+    memory: &'a mut Memory,
+    monitor: &'a [u8],
+    video: &'a mut Cdp1861,
+    keypad: &'a Keypad,
+    key: &'a mut u8,
+}
+
+impl Bus for VipBus<'_> {
+    fn read (&mut self, address: u16) -> u8 {
+        if address < MONITOR_START {
+            self.memory.ram[address as usize % self.memory.ram.len()]
+        } else {
+            self.monitor.get(address as usize % MONITOR_SIZE).copied().unwrap_or(0)
+        }
+    }
+
+    fn write (&mut self, address: u16, value: u8) {
+        if address < MONITOR_START {
+            let size = self.memory.ram.len();
+            self.memory.ram[address as usize % size] = value;
+        }
+    }
+
+    fn output (&mut self, port: u8, value: u8) {
+        match port {
+            1   =>  self.video.enabled = false,
+            2   =>  *self.key = value & 0xF,
+            _   =>  {},
+        }
+    }
+
+    fn input (&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.video.enabled = true;
+        }
+
+        0
+    }
+
+    fn flag (&mut self, n: u8) -> bool {
+        match n {
+            1   =>  self.video.ef1(),
+            3   =>  self.keypad.state[*self.key as usize],
+            _   =>  false,
+        }
+    }
+}
+
+impl Vip {
+    /**
+     * The interpreter image is loaded at 0x0000, over the program's reserved area, and truncated so it does not overwrite the program.
+     * The monitor ROM is optional, but the interpreter reads its font from there.
+     */
+    pub fn new (interpreter: &[u8], monitor: &[u8], memory: &mut Memory) -> Self {
+        if interpreter.len() > PROGRAM_START {
+            log::warn!("Truncating interpreter image of {} bytes to {}", interpreter.len(), PROGRAM_START);
+        }
+
+        let mut vip = Self {
+            cpu: Cdp1802::new(),
+            video: Cdp1861::new(),
+            key: 0,
+            frames: 0,
+            cycles: 0,
+            interpreter: interpreter.iter().copied().take(PROGRAM_START).collect(),
+            monitor: monitor.iter().copied().take(MONITOR_SIZE).collect(),
+        };
+
+        vip.reset(memory);
+        vip
+    }
+
+    pub fn reset (&mut self, memory: &mut Memory) {
+        self.cpu = Cdp1802::new();
+        self.video = Cdp1861::new();
+        self.key = 0;
+        self.frames = 0;
+        self.cycles = 0;
+        memory.ram[.. self.interpreter.len()].copy_from_slice(&self.interpreter);
+    }
+
+    /**
+     * Runs one CDP1802 instruction, then lets the CDP1861 catch up, interrupting and stealing DMA cycles as it scans lines
+     */
+    pub fn step (&mut self, memory: &mut Memory, display: &mut Display, keypad: &Keypad) {
+        let mut bus = VipBus {
+            memory,
+            monitor: &self.monitor,
+            video: &mut self.video,
+            keypad,
+            key: &mut self.key,
+        };
+        let line = bus.video.line();
+        let cycles = self.cpu.step(&mut bus);
+
+        bus.video.cycle += cycles;
+        self.cycles += 1;
+
+        for next in line + 1 ..= bus.video.line() {
+            if next == INTERRUPT_LINE && bus.video.enabled {
+                bus.video.interrupt = true;
+            }
+
+            if bus.video.displays(next) {
+                for offset in 0..DMA_BYTES {
+                    let byte = self.cpu.dma_out(&mut bus);
+                    bus.video.draw(display, next, offset, byte);
+                }
+
+                bus.video.cycle += DMA_BYTES;
+            }
+        }
+
+        if bus.video.interrupt && self.cpu.interrupt() {
+            bus.video.interrupt = false;
+        }
+
+        if bus.video.cycle >= FRAME_CYCLES {
+            bus.video.cycle -= FRAME_CYCLES;
+            self.frames += 1;
+        }
+    }
+
+    pub fn beep (&self) -> bool {
+        self.cpu.q
+    }
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    pub fn machine (&self) -> Machine {
+        match (&self.vip, &self.keypad2) {
+            (Some(_), _)    =>  Machine::CosmacVip,
+            (None, Some(_)) =>  Machine::Chip8X,
+            (None, None)    =>  Machine::Chip8,
+        }
+    }
+
+    /**
+     * Switches to the low-level COSMAC VIP and restarts the ROM under the given interpreter image
+     */
+    pub fn load_vip (&mut self, interpreter: &[u8], monitor: Option<Vec<u8>>) {
+        // CHIP-8X programs are not loaded where the VIP interpreter expects them
+        if self.keypad2.take().is_some() {
+            self.load_address = PROGRAM_START;
+        }
+
+        self.vip = Some(Box::new(Vip::new(interpreter, &monitor.unwrap_or_default(), &mut self.memory)));
+        self.reset();
+    }
+
+    /**
+     * Switches back to the high-level interpreter and restarts the ROM
+     */
+    pub fn unload_vip (&mut self) {
+        self.vip = None;
+        self.reset();
+    }
+}
+
+#[test]
+fn display () {
+    let interpreter = [
+        0xF8, 0x20, 0xA1,   // R1 = 0020, interrupt routine
+        0xF8, 0x40, 0xA2,   // R2 = 0040, stack
+        0xF8, 0x0C, 0xA3,   // R3 = 000C, program counter
+        0xD3,               // SEP R3
+        0x00, 0x00,
+        0xE2,               // SEX R2
+        0x69,               // INP 1: enable display
+        0x30, 0x0E,         // BR 0E
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x70,               // RET, right before the interrupt entry point
+        0x22, 0x78,         // DEC R2, SAV
+        0xF8, 0x0F, 0xB0,   // R0 = 0F00, display page
+        0xF8, 0x00, 0xA0,
+        0x30, 0x1F,         // BR 1F
+    ];
+    let mut memory = Memory::new(&[0x12, 0x00], PROGRAM_START).unwrap();
+    let mut display = Display::new();
+    let keypad = Keypad::new();
+    let mut vip = Vip::new(&interpreter, &[], &mut memory);
+
+    memory.ram[0xF00] = 0xF0;
+    memory.ram[0xF20] = 0x0F;
+
+    while vip.frames < 2 {
+        vip.step(&mut memory, &mut display, &keypad);
+    }
+
+    assert_eq!(&display.framebuffer[0..8], &[true, true, true, true, false, false, false, false]);
+    assert_eq!(&display.framebuffer[64..72], &[false, false, false, false, true, true, true, true]);
+}
+
+#[test]
+fn held_interrupt () {
+    // BR 00
+    let interpreter = [0x30, 0x00];
+    let mut memory = Memory::new(&[0x12, 0x00], PROGRAM_START).unwrap();
+    let mut display = Display::new();
+    let keypad = Keypad::new();
+    let mut vip = Vip::new(&interpreter, &[], &mut memory);
+
+    vip.video.enabled = true;
+    vip.cpu.ie = false;
+    while vip.video.line() <= INTERRUPT_LINE {
+        vip.step(&mut memory, &mut display, &keypad);
+    }
+    assert!(vip.video.interrupt);
+
+    vip.cpu.ie = true;
+    vip.step(&mut memory, &mut display, &keypad);
+    assert!(!vip.video.interrupt);
+    assert_eq!((vip.cpu.x, vip.cpu.p), (2, 1));
+}
+
+#[test]
+fn interpreter_size () {
+    let mut memory = Memory::new(&[0x12, 0x00], PROGRAM_START).unwrap();
+    let vip = Vip::new(&[0xAA; 0x300], &[], &mut memory);
+
+    assert_eq!(memory.ram[PROGRAM_START - 1], 0xAA);
+    assert_eq!(&memory.ram[PROGRAM_START..PROGRAM_START + 2], &[0x12, 0x00]);
+    assert_eq!(vip.interpreter.len(), PROGRAM_START);
+}
@@ -1,21 +1,19 @@
 /**
- * oscillator -> gain -> analyzer -> destination
+ * samples -> gain -> analyzer -> destination
+ * Samples are generated by the emulator, and queued back to back.
  */
 export class Audio {
     #context: AudioContext;
-    #oscillator: OscillatorNode;
     #gain: GainNode;
     #analyzer: AnalyserNode;
+    #time = 0;
     data: {
         timeDomain: Uint8Array,
         frequency: Uint8Array,
     };
 
-    constructor (type: OscillatorType = 'sine', frequency = 440) {
+    constructor () {
         this.#context = new AudioContext();
-        this.#oscillator = this.#context.createOscillator()
-        this.#oscillator.type = type;
-        this.#oscillator.frequency.value = frequency;
         this.#gain = this.#context.createGain();
         this.#gain.gain.value = 1;
         this.#analyzer = this.#context.createAnalyser();
@@ -28,7 +26,6 @@ export class Audio {
         };
 
         this.#gain.connect(this.#analyzer);
-        this.#oscillator.start();
     }
 
     start () {
@@ -39,14 +36,23 @@ export class Audio {
         this.#analyzer.disconnect();
     }
 
-    play () {
-        this.analyze();
-        this.#oscillator.connect(this.#gain);
-    }
+    /**
+     * Plays samples right after the ones previously queued
+     */
+    queue (samples: Float32Array) {
+        if (samples.length > 0) {
+            const buffer = this.#context.createBuffer(1, samples.length, this.sampleRate);
+            const source = this.#context.createBufferSource();
+
+            buffer.copyToChannel(samples, 0);
+            source.buffer = buffer;
+            source.connect(this.#gain);
+            this.#time = Math.max(this.#time, this.#context.currentTime);
+            source.start(this.#time);
+            this.#time += buffer.duration;
+        }
 
-    pause () {
         this.analyze();
-        this.#oscillator.disconnect();
     }
 
     analyze () {
@@ -58,19 +64,7 @@ export class Audio {
         return this.#context.sampleRate;
     }
 
-    get type () {
-        return this.#oscillator.type;        
-    }
-
     set volume (volume: number) {
         this.#gain.gain.value = volume;
     }
-
-    set type (type: OscillatorType) {
-        this.#oscillator.type = type;
-    }
-
-    set frequency (frequency: number) {
-        this.#oscillator.frequency.value = frequency;
-    }
 }
@@ -1,7 +1,7 @@
 import GameStats from 'game-stats';
 
 import wasm from '../backend/pkg/index_bg.wasm';
-import init, { Emulator, set_panic_hook } from '../backend/pkg';
+import init, { Emulator, PixelFormat, Platform, RunState, ScaleFilter, ScaleOverlay, Theme, TurboAudio, Waveform, database_import, set_panic_hook } from '../backend/pkg';
 import { Debug } from './debug';
 import { Logs } from './logs';
 import { Audio } from './audio';
@@ -22,6 +22,7 @@ export enum Status {
 export class Chip8 {
     static VIDEO_WIDTH = 64;
     static VIDEO_HEIGHT = 32;
+    static BEEP_FREQUENCY = 440;
     
     canvas: HTMLCanvasElement;
     error: Error;
@@ -38,22 +39,46 @@ export class Chip8 {
 
     static async new (rom) {
         const { memory } = await init(wasm);
-        return new Chip8(rom, memory);
+        return new Chip8(Emulator.new(rom), memory);
     }
 
-    private constructor (rom, memory) {
-        this.#vm = Emulator.new(rom);
+    /**
+     * Loads an Octo cartridge GIF, with its speed, quirks and colors
+     */
+    static async fromCartridge (gif: Uint8Array) {
+        const { memory } = await init(wasm);
+        return new Chip8(Emulator.from_cartridge(gif), memory);
+    }
+
+    /**
+     * Adds programs from the community database's `programs.json`, detected by ROMs loaded afterwards
+     */
+    static async importDatabase (programs: string) {
+        await init(wasm);
+        return database_import(programs);
+    }
+
+    private constructor (vm: Emulator, memory) {
+        this.#vm = vm;
         this.#stats = new GameStats({ historyLimit: 100 });
         this.logs = new Logs();
         this.memory = memory;
         this.audio = new Audio();
+        this.#vm.set_audio(this.audio.sampleRate, Waveform.Square, Chip8.BEEP_FREQUENCY, 0.25);
 
         set_panic_hook((message) => this.stop(new Error(message)));
     }
 
     start () {
+        this.#vm.resume();
+
         const rafCallback = (timestamp) => {
-            this.cycleUntil('timer');
+            this.cycle(() => {
+                const status = this.#vm.run_frame();
+                const presented = status.presented;
+                status.free();
+                return presented;
+            });
             this.#stats.record(timestamp);
             // Don't run another frame if it has been canceled in the mean time
             if (this.#rafHandle) {
@@ -67,6 +92,7 @@ export class Chip8 {
     }
 
     stop (error?: Error) {
+        this.#vm.pause();
         this.audio.stop();
         cancelAnimationFrame(this.#rafHandle);
         this.#rafHandle = null;
@@ -79,22 +105,39 @@ export class Chip8 {
         this.onStatus?.();
     }
 
+    /**
+     * `fn` either runs a frame through `run_frame` and returns whether it was presented, or steps the emulator and returns nothing
+     */
     private cycle (fn) {
         try {
-            fn();
+            const presented = fn();
+
+            this.audio.queue(this.#vm.audio_samples(this.#vm.audio_available()));
+
+            // Frames skipped in turbo are not presented
+            if (presented === false) {
+                return;
+            } else if (presented === undefined) {
+                this.#vm.refresh_framebuffer();
+            }
+
             this.debug = new Debug(this.#vm);
             this.render();
-            if (this.#vm.beep()) {
-                this.audio.play();
-            } else {
-                this.audio.pause();
-            }
         } catch (err) {
             // Don't call stop() here, because the original error will already be caught by the panic hook
             console.error(err);
         } finally {
             this.onCycle?.();
         }
+
+        switch (this.#vm.state()) {
+            case RunState.Error: this.stop(new Error(this.#vm.error())); break;
+            case RunState.Breakpoint: this.stop(); break;
+        }
+    }
+
+    frameAdvance () {
+        this.cycle(this.#vm.frame_advance.bind(this.#vm));
     }
 
     cycleUntil (duration) {
@@ -107,13 +150,25 @@ export class Chip8 {
     }
 
     private render () {
-        this.canvas?.getContext('2d').putImageData(new ImageData(new Uint8ClampedArray(this.#vm.get_framebuffer()), Chip8.VIDEO_WIDTH, Chip8.VIDEO_HEIGHT), 0, 0);
+        // View over the emulator's persistent framebuffer, recreated every time in case wasm memory has grown.
+        // Other pixel formats are meant for hosts reading the buffer themselves, so the canvas gets an RGBA copy instead.
+        const framebuffer = this.#vm.pixel_format() === PixelFormat.Rgba8888
+            ? new Uint8ClampedArray(this.memory.buffer, this.#vm.framebuffer_ptr(), this.#vm.framebuffer_len())
+            : new Uint8ClampedArray(this.#vm.get_framebuffer());
+        this.canvas?.getContext('2d').putImageData(new ImageData(framebuffer, Chip8.VIDEO_WIDTH, Chip8.VIDEO_HEIGHT), 0, 0);
     }
 
     input (key: Button, state: boolean) {
         this.#vm.update_key(key, state);
     }
 
+    /**
+     * Maps a host input (`KeyboardEvent.key`, or `gamepad:N`) to a key, and returns whether it is bound
+     */
+    hostInput (input: string, state: boolean) {
+        return this.#vm.input_update(input, state);
+    }
+
     get status () {
         if (this.error) {
             return Status.ERROR;
@@ -124,6 +179,90 @@ export class Chip8 {
         }
     }
 
+    get cpuRate () {
+        return this.#vm.cpu_rate();
+    }
+
+    set cpuRate (rate: number) {
+        this.#vm.set_cpu_rate(rate);
+    }
+
+    get instructionsPerFrame () {
+        return this.#vm.instructions_per_frame();
+    }
+
+    set instructionsPerFrame (ipf: number) {
+        this.#vm.set_instructions_per_frame(ipf);
+    }
+
+    get vipTiming () {
+        return this.#vm.vip_timing();
+    }
+
+    useVipTiming () {
+        this.#vm.set_vip_timing();
+    }
+
+    /**
+     * Runs the ROM on an emulated COSMAC VIP, under an image of the original interpreter
+     */
+    loadVip (interpreter: Uint8Array, monitor?: Uint8Array) {
+        this.#vm.load_vip(interpreter, monitor);
+    }
+
+    unloadVip () {
+        this.#vm.unload_vip();
+    }
+
+    /**
+     * Runs the ROM as a `.c8x` CHIP-8X program, in color and with a second keypad
+     */
+    loadChip8X () {
+        this.#vm.load_chip8x();
+    }
+
+    unloadChip8X () {
+        this.#vm.unload_chip8x();
+    }
+
+    input2 (key: Button, state: boolean) {
+        this.#vm.update_key2(key, state);
+    }
+
+    /**
+     * Platform detected from the ROM database, or set with `platform =`
+     */
+    get platform () {
+        return this.#vm.platform();
+    }
+
+    set platform (platform: Platform) {
+        this.#vm.set_platform(platform);
+    }
+
+    turbo (speed: number, frameSkip = speed, audio = TurboAudio.Mute) {
+        this.#vm.set_turbo(speed, frameSkip, audio);
+    }
+
+    clearTurbo () {
+        this.#vm.clear_turbo();
+    }
+
+    /**
+     * PNG of the exact emulator pixels
+     */
+    screenshot (filter = ScaleFilter.Nearest, factor = 1, overlay = ScaleOverlay.None) {
+        return new Blob([this.#vm.screenshot(filter, factor, overlay)], { type: 'image/png' });
+    }
+
+    /**
+     * Recolors the display with a built-in palette
+     */
+    theme (theme: Theme) {
+        this.#vm.set_theme(theme);
+        this.render();
+    }
+
     get performance () {
         return this.#stats.stats();
     }